@@ -1,8 +1,266 @@
+#[allow(clippy::upper_case_acronyms)]
 pub struct APOD {
   pub id: Option<u32>,
   pub date: String,
-  pub img_url: String,
+  /// All images and videos shown on the page, starting with the primary one.
+  pub media: Vec<Media>,
   pub title: String,
   pub description: String,
   pub meta: String,
+  /// Title, explanation and meta block as sanitized HTML.
+  pub html: HtmlText,
+  pub credits: Vec<Credit>,
+  pub license: License,
+  /// The page's description meta tag.
+  pub summary: Option<String>,
+  pub tags: Vec<Tag>,
+  pub links: Vec<Link>,
+  /// Dates of earlier or later APODs the explanation or meta block link to.
+  pub references: Vec<String>,
+  /// Problems found while normalizing the page, like unparseable link targets.
+  pub warnings: Vec<String>,
+  /// Fixes applied to malformed HTML of the page.
+  pub repairs: Vec<Repair>,
+}
+
+impl APOD {
+  pub fn primary_media(&self) -> &Media {
+    &self.media[0]
+  }
+
+  pub fn copyright_holders(&self) -> Vec<&Contributor> {
+    self
+      .credits
+      .iter()
+      .filter(|credit| credit.role == COPYRIGHT_ROLE)
+      .map(|credit| &credit.contributor)
+      .collect()
+  }
+}
+
+pub const COPYRIGHT_ROLE: &str = "Copyright";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LicenseClass {
+  /// Works of NASA and other sources explicitly in the public domain.
+  PublicDomain,
+  CreativeCommons,
+  Copyrighted,
+  Unknown,
+}
+
+impl LicenseClass {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LicenseClass::PublicDomain => "public_domain",
+      LicenseClass::CreativeCommons => "creative_commons",
+      LicenseClass::Copyrighted => "copyrighted",
+      LicenseClass::Unknown => "unknown",
+    }
+  }
+
+  /// Parses a class as stored by `as_str`. Anything else is unknown.
+  pub fn parse(class: &str) -> LicenseClass {
+    match class {
+      "public_domain" => LicenseClass::PublicDomain,
+      "creative_commons" => LicenseClass::CreativeCommons,
+      "copyrighted" => LicenseClass::Copyrighted,
+      _ => LicenseClass::Unknown,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct License {
+  pub class: LicenseClass,
+  /// The exact license, e.g. `CC BY-SA 4.0` for Creative Commons licenses.
+  pub variant: Option<String>,
+  pub url: Option<String>,
+}
+
+impl License {
+  pub fn new(class: LicenseClass) -> Self {
+    Self {
+      class,
+      variant: None,
+      url: None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkSection {
+  Explanation,
+  Meta,
+}
+
+impl LinkSection {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LinkSection::Explanation => "explanation",
+      LinkSection::Meta => "meta",
+    }
+  }
+}
+
+/// An outgoing link of the explanation or the meta block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+  pub section: LinkSection,
+  /// The index of the link within its section.
+  pub position: u32,
+  pub text: String,
+  pub url: String,
+  pub domain: Option<String>,
+}
+
+/// Texts of an APOD as HTML that is safe to serve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlText {
+  pub title: String,
+  pub description: String,
+  pub meta: String,
+}
+
+/// The part of an APOD page a text comes from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextSection {
+  Title,
+  Explanation,
+  Meta,
+}
+
+impl TextSection {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      TextSection::Title => "title",
+      TextSection::Explanation => "explanation",
+      TextSection::Meta => "meta",
+    }
+  }
+}
+
+/// A fix the normalization applied to malformed HTML.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repair {
+  /// Name of the rule that fired, like `missing-quotes`.
+  pub rule: String,
+  pub section: TextSection,
+  /// Byte offset of the repaired snippet in the section's HTML as it was when
+  /// the rule was applied.
+  pub offset: u32,
+  pub before: String,
+  pub after: String,
+}
+
+/// A topic from the page's keywords.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+  /// The case-folded name identifying the tag across APODs.
+  pub key: String,
+  pub name: String,
+}
+
+/// A person or organization named in the credits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contributor {
+  pub name: String,
+  pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credit {
+  /// The label the contributor is listed under, e.g. `Image Credit` or `Text`.
+  /// Copyright holders have the role `COPYRIGHT_ROLE`.
+  pub role: String,
+  pub contributor: Contributor,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaRole {
+  Primary,
+  /// The same subject in another kind of media, e.g. a still image for a video.
+  Alternate,
+  /// Another image to compare the primary one with, e.g. in image pairs.
+  Comparison,
+  /// A version of another image with labels or markings.
+  Annotated,
+}
+
+impl MediaRole {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      MediaRole::Primary => "primary",
+      MediaRole::Alternate => "alternate",
+      MediaRole::Comparison => "comparison",
+      MediaRole::Annotated => "annotated",
+    }
+  }
+}
+
+pub struct Media {
+  pub role: MediaRole,
+  /// The resource shown on the page: an image (usually a downscaled preview)
+  /// or the source of an embedded video.
+  pub img: ImageFile,
+  /// The image the preview links to, usually the full-resolution original.
+  pub hires_img: Option<ImageFile>,
+  pub video: Option<Video>,
+}
+
+impl Media {
+  pub fn new(role: MediaRole, url: String) -> Self {
+    Self {
+      role,
+      img: ImageFile::new(url),
+      hires_img: None,
+      video: None,
+    }
+  }
+}
+
+pub struct ImageFile {
+  pub url: String,
+  pub width: Option<u32>,
+  pub height: Option<u32>,
+  pub byte_size: Option<u64>,
+}
+
+impl ImageFile {
+  pub fn new(url: String) -> Self {
+    Self {
+      url,
+      width: None,
+      height: None,
+      byte_size: None,
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VideoProvider {
+  YouTube,
+  Vimeo,
+  SelfHosted,
+}
+
+impl VideoProvider {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      VideoProvider::YouTube => "youtube",
+      VideoProvider::Vimeo => "vimeo",
+      VideoProvider::SelfHosted => "self_hosted",
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Video {
+  pub provider: VideoProvider,
+  /// The provider's ID of the video. Self-hosted videos have none.
+  pub id: Option<String>,
+  pub watch_url: String,
+  pub embed_url: String,
+  /// A still image the page shows before the video is played.
+  pub poster_url: Option<String>,
 }
@@ -0,0 +1,261 @@
+use reqwest::{ClientBuilder, Proxy};
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_HOST_DELAY: Duration = Duration::from_millis(500);
+const DEFAULT_HOST_CONCURRENCY: usize = 2;
+const DEFAULT_MAX_DOWNLOAD_SIZE: u64 = 200 * 1024 * 1024;
+
+/// Settings overriding the defaults for requests to one host.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostSettings {
+  pub request_timeout: Option<Duration>,
+  pub max_attempts: Option<u32>,
+  pub delay: Option<Duration>,
+  pub concurrency: Option<usize>,
+}
+
+/// How the scraper's HTTP client connects and identifies itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientConfig {
+  pub connect_timeout: Duration,
+  /// Time a whole request may take, including reading the body. Streamed
+  /// downloads instead may take this long for the response to arrive and
+  /// between two chunks of the body, as large files take longer as a whole.
+  pub request_timeout: Duration,
+  pub http_proxy: Option<String>,
+  pub https_proxy: Option<String>,
+  /// How the operator of BPOD can be reached, e.g. an email address or URL.
+  /// It is sent as part of the user agent.
+  pub contact: String,
+  /// Minimum time between the starts of two requests to the same host. A
+  /// longer `Crawl-delay` in the host's robots.txt takes precedence.
+  pub host_delay: Duration,
+  /// Maximum number of requests to the same host at a time.
+  pub host_concurrency: usize,
+  pub hosts: HashMap<String, HostSettings>,
+  /// Where responses are cached. `None` disables the cache.
+  pub cache_dir: Option<PathBuf>,
+  /// Maximum size of downloaded images in bytes.
+  pub max_download_size: u64,
+}
+
+impl Default for ClientConfig {
+  fn default() -> Self {
+    Self {
+      connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+      request_timeout: DEFAULT_REQUEST_TIMEOUT,
+      http_proxy: None,
+      https_proxy: None,
+      contact: String::from(env!("CARGO_PKG_AUTHORS")),
+      host_delay: DEFAULT_HOST_DELAY,
+      host_concurrency: DEFAULT_HOST_CONCURRENCY,
+      hosts: HashMap::new(),
+      cache_dir: dirs::home_dir().map(|home| home.join("bpod").join("http-cache")),
+      max_download_size: DEFAULT_MAX_DOWNLOAD_SIZE,
+    }
+  }
+}
+
+impl ClientConfig {
+  /// Reads the configuration from environment variables, using the default for
+  /// every variable that is not set:
+  ///
+  /// - `BPOD_CONNECT_TIMEOUT` and `BPOD_REQUEST_TIMEOUT` in seconds
+  /// - `BPOD_HTTP_PROXY` and `BPOD_HTTPS_PROXY` as proxy URLs
+  /// - `BPOD_CONTACT` as contact info for the user agent
+  /// - `BPOD_HOST_DELAY` in seconds and `BPOD_HOST_CONCURRENCY`
+  /// - `BPOD_HOST_SETTINGS` as parsed by `parse_host_settings`
+  /// - `BPOD_HTTP_CACHE_DIR` as cache directory, or `off` to disable caching
+  /// - `BPOD_MAX_DOWNLOAD_SIZE` in bytes
+  pub fn from_env() -> Result<Self, String> {
+    let mut config = Self::default();
+    if let Some(timeout) = get_var("BPOD_CONNECT_TIMEOUT") {
+      config.connect_timeout = parse_seconds(&timeout)?;
+    }
+    if let Some(timeout) = get_var("BPOD_REQUEST_TIMEOUT") {
+      config.request_timeout = parse_seconds(&timeout)?;
+    }
+    config.http_proxy = get_var("BPOD_HTTP_PROXY");
+    config.https_proxy = get_var("BPOD_HTTPS_PROXY");
+    if let Some(contact) = get_var("BPOD_CONTACT") {
+      config.contact = contact;
+    }
+    if let Some(delay) = get_var("BPOD_HOST_DELAY") {
+      config.host_delay = parse_seconds(&delay)?;
+    }
+    if let Some(concurrency) = get_var("BPOD_HOST_CONCURRENCY") {
+      config.host_concurrency = parse_count(&concurrency)?;
+    }
+    if let Some(spec) = get_var("BPOD_HOST_SETTINGS") {
+      config.hosts = parse_host_settings(&spec)?;
+    }
+    match get_var("BPOD_HTTP_CACHE_DIR").as_deref() {
+      Some("off") => config.cache_dir = None,
+      Some(directory) => config.cache_dir = Some(PathBuf::from(directory)),
+      None => (),
+    }
+    if let Some(size) = get_var("BPOD_MAX_DOWNLOAD_SIZE") {
+      config.max_download_size = parse_count(&size)?;
+    }
+    Ok(config)
+  }
+
+  /// Identifies BPOD as a bot and tells site operators whom to contact.
+  pub fn user_agent(&self) -> String {
+    format!(
+      "BPOD/{} (APOD archive bot; +{})",
+      env!("CARGO_PKG_VERSION"),
+      self.contact
+    )
+  }
+
+  /// A client builder with the user agent, connect timeout and proxies of the
+  /// configuration. The request timeout is up to the single requests, see
+  /// `request_timeout`.
+  pub fn client_builder(&self) -> Result<ClientBuilder, String> {
+    let mut builder = ClientBuilder::new()
+      .user_agent(self.user_agent())
+      .connect_timeout(self.connect_timeout);
+    if let Some(proxy) = self.http_proxy.as_ref() {
+      let proxy = Proxy::http(proxy).map_err(|err| format!("Invalid HTTP proxy: {}", err))?;
+      builder = builder.proxy(proxy);
+    }
+    if let Some(proxy) = self.https_proxy.as_ref() {
+      let proxy = Proxy::https(proxy).map_err(|err| format!("Invalid HTTPS proxy: {}", err))?;
+      builder = builder.proxy(proxy);
+    }
+    Ok(builder)
+  }
+
+  pub fn host_settings(&self, host: &str) -> Option<&HostSettings> {
+    self.hosts.get(&host.to_lowercase())
+  }
+
+  pub fn request_timeout(&self, host: &str) -> Duration {
+    self
+      .host_settings(host)
+      .and_then(|settings| settings.request_timeout)
+      .unwrap_or(self.request_timeout)
+  }
+
+  pub fn host_delay(&self, host: &str) -> Duration {
+    self
+      .host_settings(host)
+      .and_then(|settings| settings.delay)
+      .unwrap_or(self.host_delay)
+  }
+
+  pub fn host_concurrency(&self, host: &str) -> usize {
+    self
+      .host_settings(host)
+      .and_then(|settings| settings.concurrency)
+      .unwrap_or(self.host_concurrency)
+  }
+}
+
+fn get_var(name: &str) -> Option<String> {
+  env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+fn parse_seconds(seconds: &str) -> Result<Duration, String> {
+  seconds
+    .trim()
+    .parse::<f64>()
+    .ok()
+    .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+    .map(Duration::from_secs_f64)
+    .ok_or(format!("Invalid number of seconds '{}'", seconds))
+}
+
+fn parse_count<T: std::str::FromStr + PartialOrd + Default>(count: &str) -> Result<T, String> {
+  count
+    .trim()
+    .parse::<T>()
+    .ok()
+    .filter(|count| *count > T::default())
+    .ok_or(format!("Invalid count '{}'", count))
+}
+
+/// Parses a semicolon-separated list of `<host>:<key>=<value>,...` entries
+/// like `apod.nasa.gov:timeout=30,attempts=8;www.youtube.com:timeout=10`.
+/// Known keys are `timeout` and `delay` in seconds, `attempts` and
+/// `concurrency`.
+pub fn parse_host_settings(spec: &str) -> Result<HashMap<String, HostSettings>, String> {
+  let mut hosts = HashMap::new();
+  for entry in spec.split(';').filter(|entry| !entry.trim().is_empty()) {
+    let mut parts = entry.splitn(2, ':');
+    let host = parts.next().unwrap_or("").trim().to_lowercase();
+    let settings_spec = parts.next().ok_or(format!("Missing settings for '{}'", host))?;
+    let mut settings = HostSettings::default();
+    for setting in settings_spec.split(',').filter(|setting| !setting.trim().is_empty()) {
+      let mut parts = setting.splitn(2, '=');
+      let key = parts.next().unwrap_or("").trim();
+      let value = parts.next().ok_or(format!("Missing value for '{}'", key))?;
+      match key {
+        "timeout" => settings.request_timeout = Some(parse_seconds(value)?),
+        "attempts" => settings.max_attempts = Some(parse_count(value)?),
+        "delay" => settings.delay = Some(parse_seconds(value)?),
+        "concurrency" => settings.concurrency = Some(parse_count(value)?),
+        other => return Err(format!("Unknown host setting '{}'", other)),
+      }
+    }
+    hosts.insert(host, settings);
+  }
+  Ok(hosts)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_host_settings() {
+    let hosts = parse_host_settings(
+      "APOD.nasa.gov:timeout=30,attempts=8,delay=2,concurrency=1; www.youtube.com:timeout=2.5",
+    )
+    .unwrap();
+    assert_eq!(
+      hosts["apod.nasa.gov"],
+      HostSettings {
+        request_timeout: Some(Duration::from_secs(30)),
+        max_attempts: Some(8),
+        delay: Some(Duration::from_secs(2)),
+        concurrency: Some(1),
+      }
+    );
+    assert_eq!(
+      hosts["www.youtube.com"].request_timeout,
+      Some(Duration::from_millis(2500))
+    );
+  }
+
+  #[test]
+  fn rejects_bad_host_settings() {
+    assert!(parse_host_settings("apod.nasa.gov").is_err());
+    assert!(parse_host_settings("apod.nasa.gov:timeout").is_err());
+    assert!(parse_host_settings("apod.nasa.gov:timeout=-1").is_err());
+    assert!(parse_host_settings("apod.nasa.gov:attempts=0").is_err());
+    assert!(parse_host_settings("apod.nasa.gov:concurrency=many").is_err());
+    assert!(parse_host_settings("apod.nasa.gov:speed=fast").is_err());
+  }
+
+  #[test]
+  fn identifies_as_bot() {
+    let config = ClientConfig {
+      contact: String::from("mailto:bpod@example.com"),
+      ..ClientConfig::default()
+    };
+    assert_eq!(
+      config.user_agent(),
+      format!(
+        "BPOD/{} (APOD archive bot; +mailto:bpod@example.com)",
+        env!("CARGO_PKG_VERSION")
+      )
+    );
+  }
+}
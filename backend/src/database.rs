@@ -1,39 +1,848 @@
+use crate::apod::{Contributor, HtmlText, License, LicenseClass, Media, Tag, APOD};
+use crate::link_check::{LinkCheck, LinkHealth};
+use crate::original_store::Original;
+use crate::renditions::Rendition;
+use crate::smart_crop::{Crop, CropRect};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, Error, GenericClient};
+
+/// Columns of the `pictures` table written by `APOD::save`, in the order of
+/// the values returned by `APOD::column_values`.
+const PICTURE_COLUMNS: [&str; 13] = [
+  "date",
+  "img_url",
+  "title",
+  "description",
+  "meta",
+  "license_class",
+  "license_variant",
+  "license_url",
+  "summary",
+  "warnings",
+  "title_html",
+  "description_html",
+  "meta_html",
+];
+
+/// Columns of the `media` table written by `Media::save`, in the order of the
+/// values returned by `Media::column_values`.
+const MEDIA_COLUMNS: [&str; 16] = [
+  "picture_id",
+  "position",
+  "role",
+  "url",
+  "width",
+  "height",
+  "byte_size",
+  "hires_url",
+  "hires_width",
+  "hires_height",
+  "hires_byte_size",
+  "video_provider",
+  "video_id",
+  "video_watch_url",
+  "video_embed_url",
+  "video_poster_url",
+];
+
+pub async fn create_tables(client: &Client) -> Result<(), Error> {
+  client
+    .batch_execute(
+      "CREATE TABLE IF NOT EXISTS pictures (
+                id SERIAL PRIMARY KEY,
+                date DATE NOT NULL,
+                img_url VARCHAR(2048) NOT NULL,
+                title TEXT NOT NULL,
+                description TEXT NOT NULL,
+                meta TEXT NOT NULL
+            );
+      CREATE UNIQUE INDEX IF NOT EXISTS pictures_date_idx ON pictures (date);
+      ALTER TABLE pictures
+        ADD COLUMN IF NOT EXISTS license_class TEXT NOT NULL DEFAULT 'unknown',
+        ADD COLUMN IF NOT EXISTS license_variant TEXT,
+        ADD COLUMN IF NOT EXISTS license_url VARCHAR(2048),
+        ADD COLUMN IF NOT EXISTS summary TEXT,
+        ADD COLUMN IF NOT EXISTS warnings TEXT[] NOT NULL DEFAULT '{}',
+        ADD COLUMN IF NOT EXISTS title_html TEXT NOT NULL DEFAULT '',
+        ADD COLUMN IF NOT EXISTS description_html TEXT NOT NULL DEFAULT '',
+        ADD COLUMN IF NOT EXISTS meta_html TEXT NOT NULL DEFAULT '';
+      CREATE TABLE IF NOT EXISTS media (
+                id SERIAL PRIMARY KEY,
+                picture_id INTEGER NOT NULL REFERENCES pictures (id) ON DELETE CASCADE,
+                position INTEGER NOT NULL,
+                role TEXT NOT NULL,
+                url VARCHAR(2048) NOT NULL,
+                width INTEGER,
+                height INTEGER,
+                byte_size BIGINT,
+                hires_url VARCHAR(2048),
+                hires_width INTEGER,
+                hires_height INTEGER,
+                hires_byte_size BIGINT,
+                video_provider TEXT,
+                video_id TEXT,
+                video_watch_url VARCHAR(2048),
+                video_embed_url VARCHAR(2048),
+                video_poster_url VARCHAR(2048),
+                UNIQUE (picture_id, position)
+            );
+      CREATE TABLE IF NOT EXISTS contributors (
+                id SERIAL PRIMARY KEY,
+                name TEXT NOT NULL,
+                url VARCHAR(2048) NOT NULL DEFAULT '',
+                UNIQUE (name, url)
+            );
+      CREATE TABLE IF NOT EXISTS credits (
+                picture_id INTEGER NOT NULL REFERENCES pictures (id) ON DELETE CASCADE,
+                position INTEGER NOT NULL,
+                role TEXT NOT NULL,
+                contributor_id INTEGER NOT NULL REFERENCES contributors (id),
+                PRIMARY KEY (picture_id, position)
+            );
+      CREATE TABLE IF NOT EXISTS tags (
+                id SERIAL PRIMARY KEY,
+                key TEXT NOT NULL UNIQUE,
+                name TEXT NOT NULL
+            );
+      CREATE TABLE IF NOT EXISTS picture_tags (
+                picture_id INTEGER NOT NULL REFERENCES pictures (id) ON DELETE CASCADE,
+                tag_id INTEGER NOT NULL REFERENCES tags (id),
+                position INTEGER NOT NULL,
+                PRIMARY KEY (picture_id, tag_id)
+            );
+      CREATE INDEX IF NOT EXISTS picture_tags_tag_idx ON picture_tags (tag_id);
+      CREATE TABLE IF NOT EXISTS links (
+                id SERIAL PRIMARY KEY,
+                picture_id INTEGER NOT NULL REFERENCES pictures (id) ON DELETE CASCADE,
+                section TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                anchor_text TEXT NOT NULL,
+                url VARCHAR(2048) NOT NULL,
+                domain TEXT,
+                UNIQUE (picture_id, section, position)
+            );
+      CREATE INDEX IF NOT EXISTS links_domain_idx ON links (domain);
+      CREATE INDEX IF NOT EXISTS links_url_idx ON links (url);
+      CREATE TABLE IF NOT EXISTS cross_references (
+                picture_id INTEGER NOT NULL REFERENCES pictures (id) ON DELETE CASCADE,
+                referenced_date DATE NOT NULL,
+                PRIMARY KEY (picture_id, referenced_date)
+            );
+      CREATE INDEX IF NOT EXISTS cross_references_date_idx
+        ON cross_references (referenced_date);
+      CREATE TABLE IF NOT EXISTS link_checks (
+                id SERIAL PRIMARY KEY,
+                url VARCHAR(2048) NOT NULL,
+                checked_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                health TEXT NOT NULL,
+                status_code INTEGER,
+                error TEXT
+            );
+      CREATE INDEX IF NOT EXISTS link_checks_url_idx ON link_checks (url, checked_at);
+      CREATE TABLE IF NOT EXISTS archived_links (
+                url VARCHAR(2048) PRIMARY KEY,
+                archive_url VARCHAR(2048) NOT NULL,
+                archived_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            );
+      CREATE TABLE IF NOT EXISTS repairs (
+                picture_id INTEGER NOT NULL REFERENCES pictures (id) ON DELETE CASCADE,
+                position INTEGER NOT NULL,
+                rule TEXT NOT NULL,
+                section TEXT NOT NULL,
+                \"offset\" INTEGER NOT NULL,
+                before TEXT NOT NULL,
+                after TEXT NOT NULL,
+                PRIMARY KEY (picture_id, position)
+            );
+      CREATE INDEX IF NOT EXISTS repairs_rule_idx ON repairs (rule);
+      CREATE TABLE IF NOT EXISTS originals (
+                sha256 CHAR(64) PRIMARY KEY,
+                format TEXT NOT NULL,
+                width INTEGER NOT NULL,
+                height INTEGER NOT NULL,
+                byte_size BIGINT NOT NULL,
+                stored_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                verified_at TIMESTAMPTZ,
+                is_intact BOOLEAN
+            );
+      CREATE TABLE IF NOT EXISTS original_urls (
+                url VARCHAR(2048) PRIMARY KEY,
+                sha256 CHAR(64) NOT NULL REFERENCES originals (sha256)
+            );
+      CREATE TABLE IF NOT EXISTS renditions (
+                picture_id INTEGER NOT NULL REFERENCES pictures (id) ON DELETE CASCADE,
+                shape TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                format TEXT NOT NULL,
+                width INTEGER NOT NULL,
+                height INTEGER NOT NULL,
+                byte_size BIGINT NOT NULL,
+                file_name TEXT NOT NULL,
+                PRIMARY KEY (picture_id, shape, size, format)
+            );
+      ALTER TABLE renditions ADD COLUMN IF NOT EXISTS recipe CHAR(64);
+      CREATE TABLE IF NOT EXISTS crops (
+                picture_id INTEGER PRIMARY KEY REFERENCES pictures (id) ON DELETE CASCADE,
+                x INTEGER NOT NULL,
+                y INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                source_width INTEGER NOT NULL,
+                source_height INTEGER NOT NULL,
+                is_manual BOOLEAN NOT NULL DEFAULT false
+            );",
+    )
+    .await
+}
+
+/// Gets the dates of all APODs referencing the APOD of the given date.
+pub async fn get_referencing_dates(client: &Client, date: &str) -> Result<Vec<String>, Error> {
+  let rows = client
+    .query(
+      "SELECT pictures.date::TEXT FROM cross_references
+        JOIN pictures ON pictures.id = cross_references.picture_id
+        WHERE cross_references.referenced_date = $1::TEXT::DATE
+        ORDER BY pictures.date;",
+      &[&date],
+    )
+    .await?;
+  Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Gets the date and the sanitized title, explanation and meta HTML of every
+/// APOD, oldest first.
+pub async fn get_html_texts(client: &Client) -> Result<Vec<(String, HtmlText)>, Error> {
+  let rows = client
+    .query(
+      "SELECT date::TEXT, title_html, description_html, meta_html FROM pictures ORDER BY date;",
+      &[],
+    )
+    .await?;
+  Ok(
+    rows
+      .iter()
+      .map(|row| {
+        let html = HtmlText {
+          title: row.get(1),
+          description: row.get(2),
+          meta: row.get(3),
+        };
+        (row.get(0), html)
+      })
+      .collect(),
+  )
+}
+
+/// How often a normalization rule fired across the archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleCount {
+  pub rule: String,
+  pub hits: i64,
+  /// Number of APODs the rule fired on.
+  pub num_pictures: i64,
+  /// Snippet before and after one of the repairs, as an example.
+  pub example: (String, String),
+}
+
+/// Counts the repairs made by each normalization rule, most frequent first.
+pub async fn get_repair_counts(client: &Client) -> Result<Vec<RuleCount>, Error> {
+  let rows = client
+    .query(
+      "SELECT rule, COUNT(*), COUNT(DISTINCT picture_id),
+          (array_agg(before ORDER BY picture_id, position))[1],
+          (array_agg(after ORDER BY picture_id, position))[1]
+        FROM repairs
+        GROUP BY rule
+        ORDER BY COUNT(*) DESC, rule;",
+      &[],
+    )
+    .await?;
+  Ok(
+    rows
+      .iter()
+      .map(|row| RuleCount {
+        rule: row.get(0),
+        hits: row.get(1),
+        num_pictures: row.get(2),
+        example: (row.get(3), row.get(4)),
+      })
+      .collect(),
+  )
+}
+
+/// Gets the hash of the original downloaded from `url`, if any.
+pub async fn get_original_sha256(client: &Client, url: &str) -> Result<Option<String>, Error> {
+  let row = client
+    .query_opt("SELECT sha256 FROM original_urls WHERE url = $1;", &[&url])
+    .await?;
+  Ok(row.map(|row| row.get(0)))
+}
+
+/// Records an original and the URL it was downloaded from.
+pub async fn save_original(client: &Client, url: &str, original: &Original) -> Result<(), Error> {
+  client
+    .execute(
+      "INSERT INTO originals (sha256, format, width, height, byte_size)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (sha256) DO NOTHING;",
+      &[
+        &original.sha256,
+        &original.format,
+        &(original.width as i32),
+        &(original.height as i32),
+        &(original.byte_size as i64),
+      ],
+    )
+    .await?;
+  client
+    .execute(
+      "INSERT INTO original_urls (url, sha256) VALUES ($1, $2)
+        ON CONFLICT (url) DO UPDATE SET sha256 = EXCLUDED.sha256;",
+      &[&url, &original.sha256],
+    )
+    .await?;
+  Ok(())
+}
+
+/// Gets the hashes of all originals, least recently verified first.
+pub async fn get_original_hashes(client: &Client) -> Result<Vec<String>, Error> {
+  let rows = client
+    .query(
+      "SELECT sha256 FROM originals ORDER BY verified_at NULLS FIRST, sha256;",
+      &[],
+    )
+    .await?;
+  Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+pub async fn save_verification(client: &Client, sha256: &str, is_intact: bool) -> Result<u64, Error> {
+  client
+    .execute(
+      "UPDATE originals SET verified_at = now(), is_intact = $2 WHERE sha256 = $1;",
+      &[&sha256, &is_intact],
+    )
+    .await
+}
+
+/// Replaces the recorded renditions of an APOD and returns the file names of
+/// the replaced renditions that are not part of the new ones.
+pub async fn save_renditions(
+  client: &Client,
+  picture_id: i32,
+  renditions: &[Rendition],
+) -> Result<Vec<String>, Error> {
+  let replaced = client
+    .query(
+      "DELETE FROM renditions WHERE picture_id = $1 RETURNING file_name;",
+      &[&picture_id],
+    )
+    .await?;
+  for rendition in renditions.iter() {
+    client
+      .execute(
+        "INSERT INTO renditions
+            (picture_id, shape, size, format, width, height, byte_size, file_name, recipe)
+          VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9);",
+        &[
+          &picture_id,
+          &rendition.shape.kind(),
+          &(rendition.shape.size() as i32),
+          &rendition.format.as_str(),
+          &(rendition.width as i32),
+          &(rendition.height as i32),
+          &(rendition.byte_size as i64),
+          &rendition.file_name,
+          &rendition.recipe,
+        ],
+      )
+      .await?;
+  }
+  Ok(
+    replaced
+      .iter()
+      .map(|row| row.get::<_, String>(0))
+      .filter(|file_name| !renditions.iter().any(|rendition| rendition.file_name == *file_name))
+      .collect(),
+  )
+}
+
+/// An APOD whose renditions can be built from a stored original.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThumbnailSource {
+  pub picture_id: i32,
+  pub date: String,
+  pub original_sha256: String,
+  pub license: License,
+  /// Recipes of the recorded renditions, empty for renditions not built from
+  /// an original.
+  pub recipes: Vec<String>,
+  pub file_names: Vec<String>,
+}
+
+/// Gets every APOD with a stored original of its primary image, oldest first.
+/// The full-size version is preferred where both versions were stored.
+pub async fn get_thumbnail_sources(client: &Client) -> Result<Vec<ThumbnailSource>, Error> {
+  let rows = client
+    .query(
+      "SELECT * FROM (
+          SELECT DISTINCT ON (pictures.id)
+              pictures.id, pictures.date::TEXT, original_urls.sha256::TEXT,
+              pictures.license_class, pictures.license_variant, pictures.license_url,
+              ARRAY(SELECT COALESCE(recipe::TEXT, '') FROM renditions
+                WHERE renditions.picture_id = pictures.id),
+              ARRAY(SELECT file_name FROM renditions WHERE renditions.picture_id = pictures.id),
+              pictures.date AS sort_date
+            FROM pictures
+            JOIN media ON media.picture_id = pictures.id AND media.position = 0
+            JOIN original_urls ON original_urls.url IN (media.hires_url, media.url)
+            ORDER BY pictures.id, (original_urls.url = media.hires_url) IS TRUE DESC
+        ) AS sources
+        ORDER BY sort_date;",
+      &[],
+    )
+    .await?;
+  Ok(
+    rows
+      .iter()
+      .map(|row| ThumbnailSource {
+        picture_id: row.get(0),
+        date: row.get(1),
+        original_sha256: row.get(2),
+        license: License {
+          class: LicenseClass::parse(row.get(3)),
+          variant: row.get(4),
+          url: row.get(5),
+        },
+        recipes: row.get(6),
+        file_names: row.get(7),
+      })
+      .collect(),
+  )
+}
+
+/// Gets the crop of the square renditions of the APOD of the given date.
+pub async fn get_crop(client: &Client, date: &str) -> Result<Option<Crop>, Error> {
+  let row = client
+    .query_opt(
+      "SELECT x, y, size, source_width, source_height, is_manual FROM crops
+        JOIN pictures ON pictures.id = crops.picture_id
+        WHERE pictures.date = $1::TEXT::DATE;",
+      &[&date],
+    )
+    .await?;
+  Ok(row.map(|row| Crop {
+    rect: CropRect {
+      x: row.get::<_, i32>(0) as u32,
+      y: row.get::<_, i32>(1) as u32,
+      size: row.get::<_, i32>(2) as u32,
+    },
+    source_width: row.get::<_, i32>(3) as u32,
+    source_height: row.get::<_, i32>(4) as u32,
+    is_manual: row.get(5),
+  }))
+}
+
+pub async fn save_crop(client: &Client, picture_id: i32, crop: &Crop) -> Result<u64, Error> {
+  client
+    .execute(
+      "INSERT INTO crops (picture_id, x, y, size, source_width, source_height, is_manual)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (picture_id) DO UPDATE SET x = EXCLUDED.x, y = EXCLUDED.y,
+          size = EXCLUDED.size, source_width = EXCLUDED.source_width,
+          source_height = EXCLUDED.source_height, is_manual = EXCLUDED.is_manual;",
+      &[
+        &picture_id,
+        &(crop.rect.x as i32),
+        &(crop.rect.y as i32),
+        &(crop.rect.size as i32),
+        &(crop.source_width as i32),
+        &(crop.source_height as i32),
+        &crop.is_manual,
+      ],
+    )
+    .await
+}
+
+/// Overrides the crop of an APOD with a rectangle in the coordinates of the
+/// image the stored crop was chosen on, or returns the crop to automatic with
+/// `None`. Returns 0 if the APOD has no crop yet or the rectangle does not fit
+/// the image.
+pub async fn override_crop(client: &Client, date: &str, rect: Option<CropRect>) -> Result<u64, Error> {
+  match rect {
+    Some(rect) => {
+      client
+        .execute(
+          "UPDATE crops SET x = $2, y = $3, size = $4, is_manual = true
+            FROM pictures
+            WHERE pictures.id = crops.picture_id AND pictures.date = $1::TEXT::DATE
+              AND $4 > 0 AND $2 + $4 <= source_width AND $3 + $4 <= source_height;",
+          &[&date, &(rect.x as i32), &(rect.y as i32), &(rect.size as i32)],
+        )
+        .await
+    }
+    None => {
+      client
+        .execute(
+          "UPDATE crops SET is_manual = false
+            FROM pictures
+            WHERE pictures.id = crops.picture_id AND pictures.date = $1::TEXT::DATE;",
+          &[&date],
+        )
+        .await
+    }
+  }
+}
+
+/// Gets up to `limit` outbound HTTP links that were not checked within the
+/// last `days` days, least recently checked first, together with the date of
+/// the earliest APOD linking to them.
+pub async fn get_links_due_for_check(
+  client: &Client,
+  days: i32,
+  limit: i64,
+) -> Result<Vec<(String, String)>, Error> {
+  let rows = client
+    .query(
+      "SELECT links.url, MIN(pictures.date)::TEXT FROM links
+        JOIN pictures ON pictures.id = links.picture_id
+        WHERE links.url ~* '^https?://'
+        GROUP BY links.url
+        HAVING NOT EXISTS (
+          SELECT 1 FROM link_checks
+            WHERE link_checks.url = links.url
+              AND link_checks.checked_at > now() - make_interval(days => $1)
+        )
+        ORDER BY (SELECT MAX(checked_at) FROM link_checks WHERE link_checks.url = links.url)
+          NULLS FIRST
+        LIMIT $2;",
+      &[&days, &limit],
+    )
+    .await?;
+  Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+pub async fn save_link_check(client: &Client, url: &str, check: &LinkCheck) -> Result<u64, Error> {
+  client
+    .execute(
+      "INSERT INTO link_checks (url, health, status_code, error) VALUES ($1, $2, $3, $4);",
+      &[
+        &url,
+        &check.health.as_str(),
+        &check.status_code.map(i32::from),
+        &check.error,
+      ],
+    )
+    .await
+}
+
+/// Whether the last `num_checks` checks of the link all found it dead.
+pub async fn is_link_dead(client: &Client, url: &str, num_checks: i64) -> Result<bool, Error> {
+  let rows = client
+    .query(
+      "SELECT health FROM link_checks WHERE url = $1 ORDER BY checked_at DESC LIMIT $2;",
+      &[&url, &num_checks],
+    )
+    .await?;
+  Ok(
+    rows.len() as i64 == num_checks
+      && rows
+        .iter()
+        .all(|row| row.get::<_, &str>(0) == LinkHealth::Dead.as_str()),
+  )
+}
+
+pub async fn get_archive_url(client: &Client, url: &str) -> Result<Option<String>, Error> {
+  let row = client
+    .query_opt("SELECT archive_url FROM archived_links WHERE url = $1;", &[&url])
+    .await?;
+  Ok(row.map(|row| row.get(0)))
+}
+
+/// Replaces a dead link by an archived copy in all explanations and meta
+/// blocks linking to it.
+pub async fn archive_link(client: &Client, url: &str, archive_url: &str) -> Result<(), Error> {
+  client
+    .execute(
+      "INSERT INTO archived_links (url, archive_url) VALUES ($1, $2)
+        ON CONFLICT (url) DO UPDATE SET archive_url = EXCLUDED.archive_url, archived_at = now();",
+      &[&url, &archive_url],
+    )
+    .await?;
+  rewrite_link(client, url, url, archive_url).await
+}
+
+/// Points links to an archived copy back to the original URL, e.g. because the
+/// page is reachable again.
+pub async fn restore_link(client: &Client, url: &str, archive_url: &str) -> Result<(), Error> {
+  client
+    .execute("DELETE FROM archived_links WHERE url = $1;", &[&url])
+    .await?;
+  rewrite_link(client, url, archive_url, url).await
+}
+
+/// Replaces a link target in the Markdown and the sanitized HTML texts of all
+/// APODs linking to `url`.
+async fn rewrite_link(client: &impl GenericClient, url: &str, from: &str, to: &str) -> Result<(), Error> {
+  let markdown_from = format!("]({})", from);
+  let markdown_to = format!("]({})", to);
+  let html_from = format!(r#"href="{}""#, escape_href(from));
+  let html_to = format!(r#"href="{}""#, escape_href(to));
+  client
+    .execute(
+      "UPDATE pictures SET
+          description = replace(description, $1, $2),
+          meta = replace(meta, $1, $2),
+          description_html = replace(description_html, $3, $4),
+          meta_html = replace(meta_html, $3, $4)
+        WHERE id IN (SELECT picture_id FROM links WHERE url = $5);",
+      &[&markdown_from, &markdown_to, &html_from, &html_to, &url],
+    )
+    .await?;
+  Ok(())
+}
+
+/// Escapes a URL the way the sanitizer writes it into `href` attributes.
+fn escape_href(url: &str) -> String {
+  url
+    .replace('&', "&amp;")
+    .replace('"', "&quot;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+}
+
 impl APOD {
-  pub fn new(
-    id: Option<u32>,
-    date: String,
-    img_url: String,
-    title: String,
-    description: String,
-    meta: String,
-  ) -> Self {
-    Self {
-      id,
-      date,
-      img_url,
-      title,
-      description,
-      meta,
-    }
-  }
-  pub async fn save(&self, client: tokio_postgres::Client) -> Result<u64, tokio_postgres::Error> {
-    match self.id {
-      Some(_) => self.update(client).await,
-      None => self.create(client).await,
-    }
-  }
-  async fn update(&self, client: tokio_postgres::Client) -> Result<u64, tokio_postgres::Error> {
+  /// Saves the APOD with all its media, credits, tags, links, references and
+  /// HTML repairs and returns its database ID. Everything is saved in one
+  /// transaction, so that a failure does not leave the APOD without its child
+  /// rows.
+  pub async fn save(&self, client: &mut Client) -> Result<i32, Error> {
+    let transaction = client.transaction().await?;
+    let id = match self.id {
+      Some(_) => self.update(&transaction).await?,
+      None => self.create(&transaction).await?,
+    };
+    transaction
+      .execute("DELETE FROM media WHERE picture_id = $1;", &[&id])
+      .await?;
+    for (position, media) in self.media.iter().enumerate() {
+      media.save(&transaction, id, position as i32).await?;
+    }
+    transaction
+      .execute("DELETE FROM credits WHERE picture_id = $1;", &[&id])
+      .await?;
+    for (position, credit) in self.credits.iter().enumerate() {
+      let contributor_id = credit.contributor.save(&transaction).await?;
+      transaction
+        .execute(
+          "INSERT INTO credits (picture_id, position, role, contributor_id) VALUES ($1, $2, $3, $4);",
+          &[&id, &(position as i32), &credit.role, &contributor_id],
+        )
+        .await?;
+    }
+    transaction
+      .execute("DELETE FROM picture_tags WHERE picture_id = $1;", &[&id])
+      .await?;
+    for (position, tag) in self.tags.iter().enumerate() {
+      let tag_id = tag.save(&transaction).await?;
+      transaction
+        .execute(
+          "INSERT INTO picture_tags (picture_id, tag_id, position) VALUES ($1, $2, $3);",
+          &[&id, &tag_id, &(position as i32)],
+        )
+        .await?;
+    }
+    transaction
+      .execute("DELETE FROM links WHERE picture_id = $1;", &[&id])
+      .await?;
+    for link in self.links.iter() {
+      transaction
+        .execute(
+          "INSERT INTO links (picture_id, section, position, anchor_text, url, domain)
+            VALUES ($1, $2, $3, $4, $5, $6);",
+          &[
+            &id,
+            &link.section.as_str(),
+            &(link.position as i32),
+            &link.text,
+            &link.url,
+            &link.domain,
+          ],
+        )
+        .await?;
+    }
+    // Links found dead earlier keep pointing to their archived copy.
+    let archived_links = transaction
+      .query(
+        "SELECT archived_links.url, archived_links.archive_url FROM archived_links
+          JOIN links ON links.url = archived_links.url
+          WHERE links.picture_id = $1;",
+        &[&id],
+      )
+      .await?;
+    for row in archived_links.iter() {
+      rewrite_link(&transaction, row.get(0), row.get(0), row.get(1)).await?;
+    }
+    transaction
+      .execute("DELETE FROM cross_references WHERE picture_id = $1;", &[&id])
+      .await?;
+    for referenced_date in self.references.iter() {
+      transaction
+        .execute(
+          "INSERT INTO cross_references (picture_id, referenced_date) VALUES ($1, $2::TEXT::DATE);",
+          &[&id, referenced_date],
+        )
+        .await?;
+    }
+    transaction
+      .execute("DELETE FROM repairs WHERE picture_id = $1;", &[&id])
+      .await?;
+    for (position, repair) in self.repairs.iter().enumerate() {
+      transaction
+        .execute(
+          "INSERT INTO repairs (picture_id, position, rule, section, \"offset\", before, after)
+            VALUES ($1, $2, $3, $4, $5, $6, $7);",
+          &[
+            &id,
+            &(position as i32),
+            &repair.rule,
+            &repair.section.as_str(),
+            &(repair.offset as i32),
+            &repair.before,
+            &repair.after,
+          ],
+        )
+        .await?;
+    }
+    transaction.commit().await?;
+    Ok(id)
+  }
+  async fn update(&self, client: &impl GenericClient) -> Result<i32, Error> {
+    let assignments: Vec<String> = PICTURE_COLUMNS
+      .iter()
+      .enumerate()
+      .map(|(i, column)| format!("{} = {}", column, placeholder(i)))
+      .collect();
     let stmt = format!(
-          "UPDATE pictures SET date = '{}', url = '{}', title = '{}', description = '{}', meta = '{}' WHERE id = {};",
-          self.date, self.img_url, self.title.replace("'", "''"), self.description.replace("'", "''"), self.meta.replace("'", "''"), self.id.unwrap()
-      );
-    client.execute(stmt.as_str(), &[]).await
+      "UPDATE pictures SET {} WHERE id = ${} RETURNING id;",
+      assignments.join(", "),
+      PICTURE_COLUMNS.len() + 1
+    );
+    let id = self.id.unwrap() as i32;
+    let mut values = self.column_values();
+    values.push(Box::new(id));
+    let row = client.query_one(stmt.as_str(), &as_params(&values)).await?;
+    Ok(row.get(0))
   }
-  async fn create(&self, client: tokio_postgres::Client) -> Result<u64, tokio_postgres::Error> {
+  async fn create(&self, client: &impl GenericClient) -> Result<i32, Error> {
+    let placeholders: Vec<String> = (0..PICTURE_COLUMNS.len()).map(placeholder).collect();
+    let assignments: Vec<String> = PICTURE_COLUMNS[1..]
+      .iter()
+      .map(|column| format!("{0} = EXCLUDED.{0}", column))
+      .collect();
     let stmt = format!(
-          "INSERT INTO pictures (date, url, title, description, meta) VALUES ('{}', '{}', '{}', '{}', '{}');",
-          self.date, self.img_url, self.title.replace("'", "''"), self.description.replace("'", "''"), self.meta.replace("'", "''")
-      );
-    client.execute(stmt.as_str(), &[]).await
+      "INSERT INTO pictures ({}) VALUES ({}) ON CONFLICT (date) DO UPDATE SET {} RETURNING id;",
+      PICTURE_COLUMNS.join(", "),
+      placeholders.join(", "),
+      assignments.join(", ")
+    );
+    let values = self.column_values();
+    let row = client.query_one(stmt.as_str(), &as_params(&values)).await?;
+    Ok(row.get(0))
+  }
+  fn column_values(&self) -> Vec<Box<dyn ToSql + Sync>> {
+    vec![
+      Box::new(self.date.clone()),
+      Box::new(self.primary_media().img.url.clone()),
+      Box::new(self.title.clone()),
+      Box::new(self.description.clone()),
+      Box::new(self.meta.clone()),
+      Box::new(self.license.class.as_str()),
+      Box::new(self.license.variant.clone()),
+      Box::new(self.license.url.clone()),
+      Box::new(self.summary.clone()),
+      Box::new(self.warnings.clone()),
+      Box::new(self.html.title.clone()),
+      Box::new(self.html.description.clone()),
+      Box::new(self.html.meta.clone()),
+    ]
   }
 }
+
+impl Media {
+  async fn save(&self, client: &impl GenericClient, picture_id: i32, position: i32) -> Result<u64, Error> {
+    let placeholders: Vec<String> = (1..=MEDIA_COLUMNS.len())
+      .map(|i| format!("${}", i))
+      .collect();
+    let stmt = format!(
+      "INSERT INTO media ({}) VALUES ({});",
+      MEDIA_COLUMNS.join(", "),
+      placeholders.join(", ")
+    );
+    let values = self.column_values(picture_id, position);
+    client.execute(stmt.as_str(), &as_params(&values)).await
+  }
+  fn column_values(&self, picture_id: i32, position: i32) -> Vec<Box<dyn ToSql + Sync>> {
+    let hires_img = self.hires_img.as_ref();
+    let video = self.video.as_ref();
+    vec![
+      Box::new(picture_id),
+      Box::new(position),
+      Box::new(self.role.as_str()),
+      Box::new(self.img.url.clone()),
+      Box::new(self.img.width.map(|w| w as i32)),
+      Box::new(self.img.height.map(|h| h as i32)),
+      Box::new(self.img.byte_size.map(|s| s as i64)),
+      Box::new(hires_img.map(|img| img.url.clone())),
+      Box::new(hires_img.and_then(|img| img.width).map(|w| w as i32)),
+      Box::new(hires_img.and_then(|img| img.height).map(|h| h as i32)),
+      Box::new(hires_img.and_then(|img| img.byte_size).map(|s| s as i64)),
+      Box::new(video.map(|video| video.provider.as_str())),
+      Box::new(video.and_then(|video| video.id.clone())),
+      Box::new(video.map(|video| video.watch_url.clone())),
+      Box::new(video.map(|video| video.embed_url.clone())),
+      Box::new(video.and_then(|video| video.poster_url.clone())),
+    ]
+  }
+}
+
+impl Contributor {
+  /// Saves the contributor unless it is already known and returns its ID.
+  async fn save(&self, client: &impl GenericClient) -> Result<i32, Error> {
+    let url = self.url.clone().unwrap_or_default();
+    let row = client
+      .query_one(
+        "INSERT INTO contributors (name, url) VALUES ($1, $2)
+          ON CONFLICT (name, url) DO UPDATE SET name = EXCLUDED.name RETURNING id;",
+        &[&self.name, &url],
+      )
+      .await?;
+    Ok(row.get(0))
+  }
+}
+
+impl Tag {
+  /// Saves the tag unless a tag with the same key is already known and
+  /// returns its ID. The name of known tags is kept.
+  async fn save(&self, client: &impl GenericClient) -> Result<i32, Error> {
+    let row = client
+      .query_one(
+        "INSERT INTO tags (key, name) VALUES ($1, $2)
+          ON CONFLICT (key) DO UPDATE SET key = EXCLUDED.key RETURNING id;",
+        &[&self.key, &self.name],
+      )
+      .await?;
+    Ok(row.get(0))
+  }
+}
+
+/// Dates are passed as text and cast in the statement so they need no
+/// conversion on the Rust side.
+fn placeholder(index: usize) -> String {
+  match index {
+    0 => String::from("$1::TEXT::DATE"),
+    _ => format!("${}", index + 1),
+  }
+}
+
+fn as_params(values: &[Box<dyn ToSql + Sync>]) -> Vec<&(dyn ToSql + Sync)> {
+  values.iter().map(|value| value.as_ref()).collect()
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Mutex as AsyncMutex, OwnedSemaphorePermit, Semaphore};
+use tokio::time::{sleep_until, Instant};
+
+struct HostSlot {
+  permits: Arc<Semaphore>,
+  next_request: AsyncMutex<Instant>,
+}
+
+/// Spaces out requests to the same host and caps how many of them run at the
+/// same time.
+#[derive(Default)]
+pub struct HostScheduler {
+  hosts: Mutex<HashMap<String, Arc<HostSlot>>>,
+}
+
+impl HostScheduler {
+  /// Waits until a request to `host` may be sent, which is at most
+  /// `concurrency` requests at a time and `delay` after the previous request
+  /// started. The request counts as running until the permit is dropped. The
+  /// concurrency of a host is fixed by its first request.
+  pub async fn acquire(&self, host: &str, delay: Duration, concurrency: usize) -> OwnedSemaphorePermit {
+    let slot = self
+      .hosts
+      .lock()
+      .unwrap()
+      .entry(host.to_lowercase())
+      .or_insert_with(|| {
+        Arc::new(HostSlot {
+          permits: Arc::new(Semaphore::new(concurrency.max(1))),
+          next_request: AsyncMutex::new(Instant::now()),
+        })
+      })
+      .clone();
+    let permit = slot.permits.clone().acquire_owned().await.unwrap();
+    let mut next_request = slot.next_request.lock().await;
+    sleep_until(*next_request).await;
+    *next_request = Instant::now() + delay;
+    permit
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn spaces_out_requests_to_same_host() {
+    let scheduler = HostScheduler::default();
+    let delay = Duration::from_millis(100);
+    let start = Instant::now();
+    drop(scheduler.acquire("apod.nasa.gov", delay, 2).await);
+    drop(scheduler.acquire("img.youtube.com", delay, 2).await);
+    assert!(start.elapsed() < delay);
+    drop(scheduler.acquire("APOD.nasa.gov", delay, 2).await);
+    assert!(start.elapsed() >= delay);
+  }
+
+  #[tokio::test]
+  async fn caps_concurrent_requests() {
+    let scheduler = Arc::new(HostScheduler::default());
+    let first = scheduler.acquire("apod.nasa.gov", Duration::from_millis(0), 1).await;
+    let waiting = {
+      let scheduler = scheduler.clone();
+      tokio::spawn(async move {
+        drop(scheduler.acquire("apod.nasa.gov", Duration::from_millis(0), 1).await);
+      })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!waiting.is_finished());
+    drop(first);
+    waiting.await.unwrap();
+  }
+}
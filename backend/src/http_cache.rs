@@ -0,0 +1,324 @@
+use crate::original_store::hex_sha256;
+use reqwest::header::{
+  HeaderMap, HeaderName, HeaderValue, CACHE_CONTROL, DATE, ETAG, EXPIRES, IF_MODIFIED_SINCE,
+  IF_NONE_MATCH, LAST_MODIFIED,
+};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+/// A response stored in the cache.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+  pub headers: HeaderMap,
+  pub body: Vec<u8>,
+  pub stored_at: SystemTime,
+}
+
+impl CacheEntry {
+  /// Whether the entry may be used without asking the server, which is the
+  /// case within the freshness lifetime of the response unless `no-cache` is
+  /// given.
+  pub fn is_fresh(&self, now: SystemTime) -> bool {
+    let directives = get_cache_directives(&self.headers);
+    if directives.no_cache {
+      return false;
+    }
+    match get_freshness_lifetime(&self.headers, &directives) {
+      Some(lifetime) => now
+        .duration_since(self.stored_at)
+        .map_or(true, |age| age < lifetime),
+      None => false,
+    }
+  }
+
+  /// Whether the entry may still be used once stale, when the server can not
+  /// be reached to revalidate it. `no-cache` and `must-revalidate` forbid this.
+  pub fn may_serve_stale(&self) -> bool {
+    let directives = get_cache_directives(&self.headers);
+    !directives.no_cache && !directives.must_revalidate
+  }
+
+  /// Headers making a request conditional on the entry being outdated.
+  pub fn validators(&self) -> HeaderMap {
+    let mut validators = HeaderMap::new();
+    if let Some(etag) = self.headers.get(ETAG) {
+      validators.insert(IF_NONE_MATCH, etag.clone());
+    }
+    if let Some(last_modified) = self.headers.get(LAST_MODIFIED) {
+      validators.insert(IF_MODIFIED_SINCE, last_modified.clone());
+    }
+    validators
+  }
+}
+
+/// Number of requests answered by the cache, by the server after a
+/// conditional request, or by the server alone.
+#[derive(Debug, Default)]
+pub struct CacheStats {
+  pub hits: AtomicU64,
+  pub revalidations: AtomicU64,
+  pub misses: AtomicU64,
+}
+
+impl Display for CacheStats {
+  fn fmt(&self, f: &mut Formatter) -> FmtResult {
+    write!(
+      f,
+      "{} hits, {} revalidated, {} misses",
+      self.hits.load(Ordering::Relaxed),
+      self.revalidations.load(Ordering::Relaxed),
+      self.misses.load(Ordering::Relaxed)
+    )
+  }
+}
+
+/// Stores responses to GET requests on disk, one file for the headers and
+/// one for the body per URL.
+pub struct HttpCache {
+  directory: PathBuf,
+  pub stats: CacheStats,
+}
+
+impl HttpCache {
+  pub fn new(directory: PathBuf) -> HttpCache {
+    HttpCache {
+      directory,
+      stats: CacheStats::default(),
+    }
+  }
+
+  pub async fn get(&self, url: &str) -> Option<CacheEntry> {
+    let (headers_path, body_path) = self.get_paths(url);
+    let stored_headers = fs::read_to_string(headers_path).await.ok()?;
+    let mut lines = stored_headers.lines();
+    if lines.next()? != url {
+      return None;
+    }
+    let stored_at = UNIX_EPOCH + Duration::from_secs(lines.next()?.parse().ok()?);
+    let mut headers = HeaderMap::new();
+    for line in lines {
+      let mut parts = line.splitn(2, ": ");
+      let name = HeaderName::from_bytes(parts.next()?.as_bytes()).ok()?;
+      let value = HeaderValue::from_str(parts.next()?).ok()?;
+      headers.append(name, value);
+    }
+    Some(CacheEntry {
+      headers,
+      body: fs::read(body_path).await.ok()?,
+      stored_at,
+    })
+  }
+
+  /// Stores a response if it can be reused: responses with `no-store` are
+  /// skipped, as are responses that are neither fresh for some time nor can be
+  /// revalidated. Both files are written to temporary files first and then
+  /// moved into place, so that readers never see partially written entries.
+  pub async fn store(&self, url: &str, entry: &CacheEntry) -> io::Result<bool> {
+    let directives = get_cache_directives(&entry.headers);
+    let has_validators = entry.headers.contains_key(ETAG) || entry.headers.contains_key(LAST_MODIFIED);
+    let lifetime = get_freshness_lifetime(&entry.headers, &directives);
+    if directives.no_store || (lifetime.is_none() && !has_validators) {
+      return Ok(false);
+    }
+
+    let stored_at = entry
+      .stored_at
+      .duration_since(UNIX_EPOCH)
+      .unwrap_or_default()
+      .as_secs();
+    let mut stored_headers = format!("{}\n{}\n", url, stored_at);
+    for (name, value) in entry.headers.iter() {
+      if let Ok(value) = value.to_str() {
+        stored_headers.push_str(&format!("{}: {}\n", name, value));
+      }
+    }
+    fs::create_dir_all(&self.directory).await?;
+    let (headers_path, body_path) = self.get_paths(url);
+    let temp_body_path = get_temp_path(&body_path);
+    let temp_headers_path = get_temp_path(&headers_path);
+    let result = async {
+      fs::write(&temp_body_path, &entry.body).await?;
+      fs::write(&temp_headers_path, stored_headers).await?;
+      fs::rename(&temp_body_path, body_path).await?;
+      fs::rename(&temp_headers_path, headers_path).await
+    }
+    .await;
+    if let Err(err) = result {
+      let _ = fs::remove_file(temp_body_path).await;
+      let _ = fs::remove_file(temp_headers_path).await;
+      return Err(err);
+    }
+    Ok(true)
+  }
+
+  /// Marks an entry as just validated by the server, taking over the headers
+  /// of the `304 Not Modified` response.
+  pub async fn refresh(&self, url: &str, mut entry: CacheEntry, headers: &HeaderMap) -> CacheEntry {
+    for (name, value) in headers.iter() {
+      entry.headers.insert(name, value.clone());
+    }
+    entry.stored_at = SystemTime::now();
+    // The refreshed entry is served anyway, even if it can not be stored.
+    let _ = self.store(url, &entry).await;
+    entry
+  }
+
+  fn get_paths(&self, url: &str) -> (PathBuf, PathBuf) {
+    let base = Path::new(&self.directory).join(hex_sha256(url.as_bytes()));
+    (base.with_extension("headers"), base.with_extension("body"))
+  }
+}
+
+/// A unique path next to `path` to write its new contents to.
+fn get_temp_path(path: &Path) -> PathBuf {
+  let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+  file_name.push(format!(".{:016x}.tmp", fastrand::u64(..)));
+  path.with_file_name(file_name)
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct CacheDirectives {
+  no_store: bool,
+  no_cache: bool,
+  /// Stale responses must not be used without revalidating them, but fresh
+  /// ones may.
+  must_revalidate: bool,
+  max_age: Option<Duration>,
+}
+
+fn get_cache_directives(headers: &HeaderMap) -> CacheDirectives {
+  let mut directives = CacheDirectives::default();
+  for value in headers.get_all(CACHE_CONTROL).iter() {
+    for directive in value.to_str().unwrap_or("").split(',') {
+      let directive = directive.trim().to_lowercase();
+      match directive.as_str() {
+        "no-store" => directives.no_store = true,
+        "no-cache" => directives.no_cache = true,
+        "must-revalidate" => directives.must_revalidate = true,
+        _ => {
+          if let Some(seconds) = directive.strip_prefix("max-age=") {
+            directives.max_age = seconds.trim_matches('"').parse().ok().map(Duration::from_secs);
+          }
+        }
+      }
+    }
+  }
+  directives
+}
+
+/// How long a response is fresh: its `max-age`, or else the time from its
+/// `Date` until its `Expires`. Invalid `Expires` dates mean the response is
+/// already stale.
+fn get_freshness_lifetime(headers: &HeaderMap, directives: &CacheDirectives) -> Option<Duration> {
+  if directives.max_age.is_some() {
+    return directives.max_age;
+  }
+  let parse_date = |name| {
+    let value = headers.get(name)?.to_str().ok()?;
+    httpdate::parse_http_date(value.trim()).ok()
+  };
+  if !headers.contains_key(EXPIRES) {
+    return None;
+  }
+  let expires = match parse_date(EXPIRES) {
+    Some(expires) => expires,
+    None => return Some(Duration::ZERO),
+  };
+  let date = parse_date(DATE)?;
+  Some(expires.duration_since(date).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{headers, temp_dir};
+
+  fn entry(pairs: &[(&'static str, &'static str)]) -> CacheEntry {
+    CacheEntry {
+      headers: headers(pairs),
+      body: b"<html>APOD</html>".to_vec(),
+      stored_at: UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+    }
+  }
+
+  #[test]
+  fn parses_cache_control() {
+    assert_eq!(
+      get_cache_directives(&headers(&[("cache-control", "public, max-age=3600")])),
+      CacheDirectives {
+        max_age: Some(Duration::from_secs(3600)),
+        ..CacheDirectives::default()
+      }
+    );
+    assert!(get_cache_directives(&headers(&[("cache-control", "No-Store")])).no_store);
+  }
+
+  #[test]
+  fn checks_freshness() {
+    let stored = entry(&[("cache-control", "max-age=60")]);
+    assert!(stored.is_fresh(stored.stored_at + Duration::from_secs(30)));
+    assert!(!stored.is_fresh(stored.stored_at + Duration::from_secs(90)));
+    let no_cache = entry(&[("cache-control", "max-age=60, no-cache")]);
+    assert!(!no_cache.is_fresh(no_cache.stored_at));
+    assert!(!no_cache.may_serve_stale());
+  }
+
+  #[test]
+  fn keeps_must_revalidate_entries_fresh_until_stale() {
+    let stored = entry(&[("cache-control", "max-age=60, must-revalidate")]);
+    assert!(stored.is_fresh(stored.stored_at + Duration::from_secs(30)));
+    assert!(!stored.is_fresh(stored.stored_at + Duration::from_secs(90)));
+    assert!(!stored.may_serve_stale());
+    assert!(entry(&[("cache-control", "max-age=60")]).may_serve_stale());
+  }
+
+  #[test]
+  fn falls_back_to_expires() {
+    let stored = entry(&[
+      ("date", "Wed, 21 Oct 2015 07:28:00 GMT"),
+      ("expires", "Wed, 21 Oct 2015 07:29:00 GMT"),
+    ]);
+    assert!(stored.is_fresh(stored.stored_at + Duration::from_secs(30)));
+    assert!(!stored.is_fresh(stored.stored_at + Duration::from_secs(90)));
+    let max_age_first = entry(&[
+      ("cache-control", "max-age=10"),
+      ("date", "Wed, 21 Oct 2015 07:28:00 GMT"),
+      ("expires", "Wed, 21 Oct 2015 07:29:00 GMT"),
+    ]);
+    assert!(!max_age_first.is_fresh(max_age_first.stored_at + Duration::from_secs(30)));
+    let invalid = entry(&[("date", "Wed, 21 Oct 2015 07:28:00 GMT"), ("expires", "0")]);
+    assert!(!invalid.is_fresh(invalid.stored_at));
+  }
+
+  #[test]
+  fn makes_conditional_requests() {
+    let validators = entry(&[
+      ("etag", "\"5f3-1a\""),
+      ("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+    ])
+    .validators();
+    assert_eq!(validators[IF_NONE_MATCH], "\"5f3-1a\"");
+    assert_eq!(validators[IF_MODIFIED_SINCE], "Wed, 21 Oct 2015 07:28:00 GMT");
+  }
+
+  #[tokio::test]
+  async fn stores_and_loads_entries() {
+    let cache = HttpCache::new(temp_dir("http-cache", "store"));
+    let url = "https://apod.nasa.gov/apod/ap210101.html";
+    let stored = entry(&[("etag", "\"abc\""), ("content-type", "text/html")]);
+    assert!(cache.store(url, &stored).await.unwrap());
+    assert_eq!(cache.get(url).await, Some(stored));
+    assert_eq!(cache.get("https://apod.nasa.gov/apod/ap210102.html").await, None);
+    assert_eq!(std::fs::read_dir(&cache.directory).unwrap().count(), 2);
+
+    let unstorable = entry(&[("content-type", "text/html")]);
+    assert!(!cache.store(url, &unstorable).await.unwrap());
+    let no_store = entry(&[("etag", "\"abc\""), ("cache-control", "no-store")]);
+    assert!(!cache.store(url, &no_store).await.unwrap());
+    std::fs::remove_dir_all(&cache.directory).unwrap();
+  }
+}
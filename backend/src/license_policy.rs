@@ -0,0 +1,140 @@
+use crate::apod::{License, LicenseClass};
+use std::env;
+
+/// How BPOD may use media of a license class, from least to most permissive.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Usage {
+  /// Only link to the media on the APOD page.
+  LinkOnly,
+  /// Show small thumbnails generated from the media.
+  Thumbnail,
+  /// Store, redisplay and serve the media itself.
+  Rehost,
+}
+
+impl Usage {
+  fn parse(usage: &str) -> Result<Usage, String> {
+    match usage.trim() {
+      "link_only" => Ok(Usage::LinkOnly),
+      "thumbnail" => Ok(Usage::Thumbnail),
+      "rehost" => Ok(Usage::Rehost),
+      other => Err(format!("Unknown usage '{}'", other)),
+    }
+  }
+}
+
+/// The permitted usage per license class.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LicensePolicy {
+  pub public_domain: Usage,
+  pub creative_commons: Usage,
+  pub copyrighted: Usage,
+  pub unknown: Usage,
+}
+
+impl Default for LicensePolicy {
+  fn default() -> Self {
+    Self {
+      public_domain: Usage::Rehost,
+      creative_commons: Usage::Rehost,
+      copyrighted: Usage::Thumbnail,
+      unknown: Usage::Thumbnail,
+    }
+  }
+}
+
+impl LicensePolicy {
+  /// Reads the policy from `BPOD_LICENSE_POLICY`, falling back to the default
+  /// for every class the variable does not mention.
+  pub fn from_env() -> Result<Self, String> {
+    match env::var("BPOD_LICENSE_POLICY") {
+      Ok(spec) => Self::parse(&spec),
+      Err(_) => Ok(Self::default()),
+    }
+  }
+
+  /// Parses a comma-separated list of `<class>=<usage>` pairs like
+  /// `copyrighted=link_only,unknown=link_only`.
+  pub fn parse(spec: &str) -> Result<Self, String> {
+    let mut policy = Self::default();
+    for entry in spec.split(',').filter(|entry| !entry.trim().is_empty()) {
+      let mut parts = entry.splitn(2, '=');
+      let class = parts.next().unwrap_or("").trim();
+      let usage = Usage::parse(parts.next().ok_or(format!("Missing usage for '{}'", class))?)?;
+      match class {
+        "public_domain" => policy.public_domain = usage,
+        "creative_commons" => policy.creative_commons = usage,
+        "copyrighted" => policy.copyrighted = usage,
+        "unknown" => policy.unknown = usage,
+        other => return Err(format!("Unknown license class '{}'", other)),
+      }
+    }
+    Ok(policy)
+  }
+
+  pub fn usage(&self, license: &License) -> Usage {
+    match license.class {
+      LicenseClass::PublicDomain => self.public_domain,
+      LicenseClass::CreativeCommons => self.creative_commons,
+      LicenseClass::Copyrighted => self.copyrighted,
+      LicenseClass::Unknown => self.unknown,
+    }
+  }
+
+  pub fn allows_thumbnail(&self, license: &License) -> bool {
+    self.usage(license) >= Usage::Thumbnail
+  }
+
+  pub fn allows_rehosting(&self, license: &License) -> bool {
+    self.usage(license) >= Usage::Rehost
+  }
+
+  /// Whether a full-resolution copy may be kept in the originals store. The
+  /// store keeps the media itself, so this needs the permission to rehost it,
+  /// even though the copy is not served.
+  pub fn allows_archiving(&self, license: &License) -> bool {
+    self.allows_rehosting(license)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_policy() {
+    let policy = LicensePolicy::parse("copyrighted=link_only, unknown=rehost").unwrap();
+    assert_eq!(policy.public_domain, Usage::Rehost);
+    assert_eq!(policy.copyrighted, Usage::LinkOnly);
+    assert_eq!(policy.unknown, Usage::Rehost);
+  }
+
+  #[test]
+  fn rejects_bad_policy() {
+    assert!(LicensePolicy::parse("copyrighted").is_err());
+    assert!(LicensePolicy::parse("copyrighted=maybe").is_err());
+    assert!(LicensePolicy::parse("secret=rehost").is_err());
+  }
+
+  #[test]
+  fn permits_usage_by_class() {
+    let policy = LicensePolicy::parse("copyrighted=link_only").unwrap();
+    let copyrighted = License::new(LicenseClass::Copyrighted);
+    let unknown = License::new(LicenseClass::Unknown);
+    let public_domain = License::new(LicenseClass::PublicDomain);
+    assert!(!policy.allows_thumbnail(&copyrighted));
+    assert!(policy.allows_thumbnail(&unknown));
+    assert!(!policy.allows_rehosting(&unknown));
+    assert!(policy.allows_rehosting(&public_domain));
+  }
+
+  #[test]
+  fn archives_only_rehostable_media() {
+    let policy = LicensePolicy::default();
+    assert!(policy.allows_thumbnail(&License::new(LicenseClass::Copyrighted)));
+    assert!(!policy.allows_archiving(&License::new(LicenseClass::Copyrighted)));
+    assert!(!policy.allows_archiving(&License::new(LicenseClass::Unknown)));
+    assert!(policy.allows_archiving(&License::new(LicenseClass::PublicDomain)));
+    assert!(policy.allows_archiving(&License::new(LicenseClass::CreativeCommons)));
+  }
+}
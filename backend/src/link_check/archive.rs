@@ -0,0 +1,112 @@
+use crate::APODRequestClient;
+use regex::Regex;
+use url::Url;
+
+/// The Wayback Machine's availability API.
+pub const WAYBACK_ENDPOINT: &str = "https://archive.org/wayback/available";
+
+/// Finds an archived copy of a page that is no longer reachable.
+pub trait ArchiveResolver {
+  /// Returns the URL of the snapshot closest to `date` (`YYYY-MM-DD`), which
+  /// usually is the day the link was published on APOD.
+  async fn resolve(&self, client: &APODRequestClient, url: &str, date: &str) -> Option<String>;
+}
+
+/// Resolves snapshots with an API compatible to the Wayback Machine's
+/// availability API.
+pub struct WaybackResolver {
+  endpoint: String,
+}
+
+impl WaybackResolver {
+  pub fn new(endpoint: &str) -> WaybackResolver {
+    WaybackResolver {
+      endpoint: String::from(endpoint),
+    }
+  }
+}
+
+impl Default for WaybackResolver {
+  fn default() -> Self {
+    Self::new(WAYBACK_ENDPOINT)
+  }
+}
+
+impl ArchiveResolver for WaybackResolver {
+  async fn resolve(&self, client: &APODRequestClient, url: &str, date: &str) -> Option<String> {
+    let timestamp = date.replace('-', "");
+    let lookup_url =
+      Url::parse_with_params(&self.endpoint, &[("url", url), ("timestamp", &timestamp)]).ok()?;
+    let response = client.get(lookup_url.as_str()).await.ok()?;
+    get_snapshot_url(&response.text().await.ok()?)
+  }
+}
+
+/// Extracts the URL of the closest snapshot from an availability response like
+/// `{"archived_snapshots": {"closest": {"available": true, "url": "..."}}}`.
+fn get_snapshot_url(response: &str) -> Option<String> {
+  let closest = Regex::new(r#""closest"\s*:\s*\{(?P<closest>[^}]*)\}"#)
+    .unwrap()
+    .captures(response)?;
+  let closest = &closest["closest"];
+  if !Regex::new(r#""available"\s*:\s*true"#)
+    .unwrap()
+    .is_match(closest)
+  {
+    return None;
+  }
+  Regex::new(r#""url"\s*:\s*"(?P<url>[^"]+)""#)
+    .unwrap()
+    .captures(closest)
+    .map(|captures| captures["url"].replace("\\/", "/"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::test_server::{test_client, TestServer};
+  use std::time::Duration;
+  use super::*;
+
+  #[test]
+  fn gets_snapshot_url() {
+    let response = r#"{"url": "example.com/m31", "archived_snapshots": {"closest": {"status": "200", "available": true, "url": "http://web.archive.org/web/20010301000000/http://example.com/m31", "timestamp": "20010301000000"}}}"#;
+    assert_eq!(
+      get_snapshot_url(response).as_deref(),
+      Some("http://web.archive.org/web/20010301000000/http://example.com/m31")
+    );
+  }
+
+  #[test]
+  fn ignores_missing_snapshots() {
+    assert_eq!(get_snapshot_url(r#"{"archived_snapshots": {}}"#), None);
+    assert_eq!(
+      get_snapshot_url(r#"{"archived_snapshots": {"closest": {"available": false, "url": "x"}}}"#),
+      None
+    );
+  }
+
+  #[tokio::test]
+  async fn resolves_against_stand_in_server() {
+    let server = TestServer::start(vec![(
+      "/wayback/available",
+      200,
+      r#"{"archived_snapshots": {"closest": {"available": true, "url": "http://web.archive.org/web/2001/http://example.com/m31"}}}"#,
+    )])
+    .await;
+    let resolver = WaybackResolver::new(&format!("{}/wayback/available", server.base_url));
+    let snapshot = resolver
+      .resolve(&test_client(Duration::from_millis(0)), "http://example.com/m31", "2001-03-01")
+      .await;
+    assert_eq!(
+      snapshot.as_deref(),
+      Some("http://web.archive.org/web/2001/http://example.com/m31")
+    );
+    assert_eq!(
+      server.requests(),
+      vec![
+        "GET /robots.txt",
+        "GET /wayback/available?url=http%3A%2F%2Fexample.com%2Fm31&timestamp=20010301"
+      ]
+    );
+  }
+}
@@ -0,0 +1,222 @@
+mod archive;
+#[cfg(test)]
+mod test_server;
+
+pub use archive::{ArchiveResolver, WaybackResolver};
+use crate::scraping::ScrapeError;
+use crate::APODRequestClient;
+use reqwest::{Method, StatusCode};
+use std::sync::Arc;
+use url::Url;
+
+/// Links are checked again once their last check is older than this.
+pub const RECHECK_AFTER_DAYS: i32 = 30;
+/// Number of consecutive checks a link has to fail before it is considered
+/// gone for good and replaced by an archived copy.
+pub const DEAD_AFTER_CHECKS: i64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkHealth {
+  Alive,
+  /// The server says the page is gone or the host does not exist anymore.
+  Dead,
+  /// The check failed in a way that may be temporary, like timeouts or server
+  /// errors.
+  Unreachable,
+  /// The site's robots.txt does not allow checking the link.
+  Disallowed,
+}
+
+impl LinkHealth {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LinkHealth::Alive => "alive",
+      LinkHealth::Dead => "dead",
+      LinkHealth::Unreachable => "unreachable",
+      LinkHealth::Disallowed => "disallowed",
+    }
+  }
+}
+
+/// The outcome of checking a link once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkCheck {
+  pub health: LinkHealth,
+  pub status_code: Option<u16>,
+  pub error: Option<String>,
+}
+
+/// Checks outbound links through the scraper's client, so that link checks
+/// respect robots.txt and share the scheduling of requests per host.
+pub struct LinkChecker {
+  client: Arc<APODRequestClient>,
+}
+
+impl LinkChecker {
+  pub fn new(client: Arc<APODRequestClient>) -> LinkChecker {
+    LinkChecker { client }
+  }
+
+  pub fn client(&self) -> &APODRequestClient {
+    &self.client
+  }
+
+  /// Checks a link with a HEAD request and falls back to GET for servers that
+  /// do not answer HEAD requests properly.
+  pub async fn check(&self, url: &str) -> LinkCheck {
+    let check = self.request(Method::HEAD, url).await;
+    match check.status_code {
+      Some(405) | Some(501) | Some(403) | Some(400) => self.request(Method::GET, url).await,
+      None if check.health == LinkHealth::Unreachable => self.request(Method::GET, url).await,
+      _ => check,
+    }
+  }
+
+  async fn request(&self, method: Method, url: &str) -> LinkCheck {
+    let parsed_url = match Url::parse(url).ok().filter(|url| url.has_host()) {
+      Some(parsed_url) => parsed_url,
+      None => {
+        return LinkCheck {
+          health: LinkHealth::Dead,
+          status_code: None,
+          error: Some(String::from("Invalid URL")),
+        }
+      }
+    };
+    let permit = match self.client.schedule(&parsed_url).await {
+      Ok(permit) => permit,
+      Err(ScrapeError::Disallowed) => {
+        return LinkCheck {
+          health: LinkHealth::Disallowed,
+          status_code: None,
+          error: Some(String::from("Disallowed by robots.txt")),
+        }
+      }
+      Err(err) => {
+        return LinkCheck {
+          health: LinkHealth::Unreachable,
+          status_code: None,
+          error: Some(format!("Could not fetch robots.txt: {}", err)),
+        }
+      }
+    };
+    let result = self.client.prepare(method, &parsed_url).send().await;
+    drop(permit);
+
+    match result {
+      Ok(response) => LinkCheck {
+        health: classify_status(response.status()),
+        status_code: Some(response.status().as_u16()),
+        error: None,
+      },
+      Err(err) => LinkCheck {
+        // Hosts that cannot be connected to at all are usually gone, while
+        // timeouts are often only temporary.
+        health: match err.is_connect() {
+          true => LinkHealth::Dead,
+          false => LinkHealth::Unreachable,
+        },
+        status_code: None,
+        error: Some(err.to_string()),
+      },
+    }
+  }
+}
+
+fn classify_status(status: StatusCode) -> LinkHealth {
+  match status.as_u16() {
+    200..=399 => LinkHealth::Alive,
+    404 | 410 => LinkHealth::Dead,
+    _ => LinkHealth::Unreachable,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::test_server::{test_client, TestServer};
+  use super::*;
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn classifies_status_codes() {
+    assert_eq!(classify_status(StatusCode::OK), LinkHealth::Alive);
+    assert_eq!(classify_status(StatusCode::NOT_FOUND), LinkHealth::Dead);
+    assert_eq!(classify_status(StatusCode::GONE), LinkHealth::Dead);
+    assert_eq!(
+      classify_status(StatusCode::SERVICE_UNAVAILABLE),
+      LinkHealth::Unreachable
+    );
+    assert_eq!(
+      classify_status(StatusCode::TOO_MANY_REQUESTS),
+      LinkHealth::Unreachable
+    );
+  }
+
+  #[tokio::test]
+  async fn checks_links() {
+    let server = TestServer::start(vec![("/alive", 200, "ok"), ("/gone", 410, "")]).await;
+    let checker = LinkChecker::new(test_client(Duration::from_millis(0)));
+
+    let alive = checker.check(&format!("{}/alive", server.base_url)).await;
+    assert_eq!(alive.health, LinkHealth::Alive);
+    assert_eq!(alive.status_code, Some(200));
+    let gone = checker.check(&format!("{}/gone", server.base_url)).await;
+    assert_eq!(gone.health, LinkHealth::Dead);
+    let missing = checker.check(&format!("{}/missing", server.base_url)).await;
+    assert_eq!(missing.status_code, Some(404));
+    assert_eq!(
+      server.requests(),
+      vec!["GET /robots.txt", "HEAD /alive", "HEAD /gone", "HEAD /missing"]
+    );
+  }
+
+  #[tokio::test]
+  async fn falls_back_to_get() {
+    let server = TestServer::start(vec![("/no-head", 405, "")]).await;
+    let checker = LinkChecker::new(test_client(Duration::from_millis(0)));
+    checker.check(&format!("{}/no-head", server.base_url)).await;
+    assert_eq!(
+      server.requests(),
+      vec!["GET /robots.txt", "HEAD /no-head", "GET /no-head"]
+    );
+  }
+
+  #[tokio::test]
+  async fn respects_robots_txt() {
+    let server = TestServer::start(vec![
+      ("/robots.txt", 200, "User-agent: *\nDisallow: /private\n"),
+      ("/", 200, ""),
+    ])
+    .await;
+    let checker = LinkChecker::new(test_client(Duration::from_millis(0)));
+    let check = checker.check(&format!("{}/private/m31", server.base_url)).await;
+    assert_eq!(check.health, LinkHealth::Disallowed);
+    assert_eq!(check.status_code, None);
+    let check = checker.check(&format!("{}/public", server.base_url)).await;
+    assert_eq!(check.health, LinkHealth::Alive);
+    assert_eq!(server.requests(), vec!["GET /robots.txt", "HEAD /public"]);
+  }
+
+  #[tokio::test]
+  async fn retries_unreachable_robots_txt() {
+    let server = TestServer::start(vec![("/robots.txt", 503, ""), ("/", 200, "")]).await;
+    let checker = LinkChecker::new(test_client(Duration::from_millis(0)));
+    let check = checker.check(&format!("{}/m31", server.base_url)).await;
+    assert_eq!(check.health, LinkHealth::Unreachable);
+
+    let result = checker.client().get(&format!("{}/m31", server.base_url)).await;
+    assert!(matches!(result, Err(ScrapeError::Status(503))));
+    // HEAD and GET of the check, then every attempt of the request.
+    assert_eq!(server.requests(), vec!["GET /robots.txt"; 2 + 5]);
+  }
+
+  #[tokio::test]
+  async fn waits_between_requests_to_same_host() {
+    let server = TestServer::start(vec![("/", 200, "")]).await;
+    let checker = LinkChecker::new(test_client(Duration::from_millis(200)));
+    let start = Instant::now();
+    checker.check(&format!("{}/a", server.base_url)).await;
+    checker.check(&format!("{}/b", server.base_url)).await;
+    assert!(start.elapsed() >= Duration::from_millis(200));
+  }
+}
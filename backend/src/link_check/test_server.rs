@@ -0,0 +1,77 @@
+use crate::client_config::ClientConfig;
+use crate::test_util;
+use crate::APODRequestClient;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A minimal HTTP server on localhost that stands in for remote hosts in tests.
+/// Every request is answered with the status and body of the first route whose
+/// path prefix matches, or with 404.
+pub struct TestServer {
+  pub base_url: String,
+  requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestServer {
+  pub async fn start(routes: Vec<(&'static str, u16, &'static str)>) -> TestServer {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded_requests = requests.clone();
+    tokio::spawn(async move {
+      loop {
+        let (mut stream, _) = match listener.accept().await {
+          Ok(connection) => connection,
+          Err(_) => return,
+        };
+        let mut head = Vec::new();
+        let mut buffer = [0; 1024];
+        while !head.ends_with(b"\r\n\r\n") {
+          match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => head.extend_from_slice(&buffer[..n]),
+          }
+        }
+        let head = String::from_utf8_lossy(&head);
+        let request_line = head.lines().next().unwrap_or_default().to_string();
+        let mut parts = request_line.split(' ');
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+        recorded_requests
+          .lock()
+          .unwrap()
+          .push(format!("{} {}", method, path));
+
+        let (status, body) = routes
+          .iter()
+          .find(|(prefix, _, _)| path.starts_with(prefix))
+          .map_or((404, ""), |(_, status, body)| (*status, *body));
+        let mut response = format!(
+          "HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+          status,
+          body.len()
+        );
+        if method != "HEAD" {
+          response.push_str(body);
+        }
+        let _ = stream.write_all(response.as_bytes()).await;
+      }
+    });
+    TestServer { base_url, requests }
+  }
+
+  /// The method and path of all requests received so far, like `HEAD /page`.
+  pub fn requests(&self) -> Vec<String> {
+    self.requests.lock().unwrap().clone()
+  }
+}
+
+/// A client that waits `host_delay` between requests to the same host.
+pub fn test_client(host_delay: Duration) -> Arc<APODRequestClient> {
+  Arc::new(test_util::test_client(ClientConfig {
+    host_delay,
+    ..ClientConfig::default()
+  }))
+}
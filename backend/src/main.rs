@@ -1,22 +1,66 @@
 mod apod;
+mod client_config;
+mod database;
+mod host_scheduler;
+mod http_cache;
+mod license_policy;
+mod link_check;
+mod original_store;
+mod renditions;
+mod robots;
 mod scraping;
+mod smart_crop;
+#[cfg(test)]
+mod test_util;
+mod worker_pool;
 
-use chrono::{TimeZone, Utc};
-use regex::Regex;
+use chrono::{NaiveDate, Utc};
+use client_config::ClientConfig;
+use database::ThumbnailSource;
+use host_scheduler::HostScheduler;
+use http_cache::{CacheEntry, HttpCache};
+use license_policy::LicensePolicy;
+use link_check::{ArchiveResolver, LinkChecker, LinkHealth, WaybackResolver};
+use original_store::{Original, OriginalStore};
+use renditions::{Rendition, RenditionSet};
+use robots::{RobotsTxt, ROBOTS_AGENT};
 use reqwest::{
-  header::{HeaderMap, HeaderValue},
-  Client, Response,
+  header::{HeaderMap, HeaderValue, IF_RANGE, RANGE},
+  Client, Method, RequestBuilder, Response, StatusCode,
 };
-use scraping::{get_apod_data, get_apod_thumbnail, ScrapeError, ScrapeResult};
-use std::{thread, time};
+use scraping::{
+  get_apod_data, get_apod_original, get_apod_thumbnail, get_original_url, lint_html, rebuild_thumbnail,
+  remove_rendition, rendition_exists, RequestOutcome, RetryPolicy, ScrapeError, ScrapeResult,
+};
+use smart_crop::{Crop, CropRect};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use std::{env, time};
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::task::JoinSet;
 use tokio_postgres::NoTls;
+use url::Url;
+use worker_pool::WorkerPool;
+
+/// How long a fetched robots.txt is used before fetching it again.
+const ROBOTS_TTL: time::Duration = time::Duration::from_secs(24 * 60 * 60);
+/// Maximum size of robots.txt files that is read, as required by RFC 9309.
+const MAX_ROBOTS_SIZE: usize = 500 * 1024;
 
 pub struct APODRequestClient {
   client: Client,
+  config: ClientConfig,
+  retry_policy: RetryPolicy,
+  cache: Option<HttpCache>,
+  scheduler: HostScheduler,
+  /// The robots.txt of every origin requested so far, with its expiry.
+  robots: Mutex<HashMap<String, (RobotsTxt, Instant)>>,
 }
 
 impl APODRequestClient {
-  fn new() -> APODRequestClient {
+  fn new(config: ClientConfig) -> Result<APODRequestClient, String> {
     let mut headers: HeaderMap = HeaderMap::new();
     headers.insert(
       "Accept-Language",
@@ -28,42 +72,276 @@ impl APODRequestClient {
     );
     headers.insert("Accept", HeaderValue::from_static("text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.9"));
 
-    let client = Client::builder()
-      .user_agent(
-          "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/89.0.4389.90 Safari/537.36 Edg/89.0.774.57",
-      )
+    let client = config
+      .client_builder()?
       .default_headers(headers)
       .build()
-      .unwrap();
-    APODRequestClient { client }
+      .map_err(|err| err.to_string())?;
+    let cache = config.cache_dir.clone().map(HttpCache::new);
+    Ok(APODRequestClient {
+      client,
+      config,
+      retry_policy: RetryPolicy::default(),
+      cache,
+      scheduler: HostScheduler::default(),
+      robots: Mutex::new(HashMap::new()),
+    })
   }
+
+  /// Gets a resource, using the HTTP cache if enabled: fresh cached responses
+  /// are returned right away, stale ones are revalidated with a conditional
+  /// request. Stale responses are served if the server can not be reached,
+  /// unless they must be revalidated.
   async fn get(&self, url: &str) -> ScrapeResult<Response> {
-    let host = Regex::new("://(.+?)[/$]")
-      .unwrap()
-      .captures(url)
-      .expect("Could not match URL")
-      .get(1)
-      .expect("Could not find host")
-      .as_str();
-
-    let num_attempts: u64 = 5;
-    for attempt in 0..num_attempts {
-      let configured_get_request = self.client.get(url).header("Host", host);
-      let response = configured_get_request.send().await;
-      if response.is_ok() {
-        return Ok(response.unwrap());
+    let cache = match self.cache.as_ref() {
+      Some(cache) => cache,
+      None => return self.request(Method::GET, url, HeaderMap::new(), false).await,
+    };
+    let entry = cache.get(url).await;
+    if let Some(entry) = entry.as_ref().filter(|entry| entry.is_fresh(SystemTime::now())) {
+      cache.stats.hits.fetch_add(1, Ordering::Relaxed);
+      return Ok(to_response(entry.clone()));
+    }
+
+    let validators = entry
+      .as_ref()
+      .map_or_else(HeaderMap::new, CacheEntry::validators);
+    let response = match self.request(Method::GET, url, validators, false).await {
+      Ok(response) => response,
+      Err(err @ ScrapeError::Network) | Err(err @ ScrapeError::Status(500..=599)) => {
+        return match entry.filter(CacheEntry::may_serve_stale) {
+          Some(entry) => {
+            cache.stats.hits.fetch_add(1, Ordering::Relaxed);
+            println!("Serving stale cached {}: {}", url, err);
+            Ok(to_response(entry))
+          }
+          None => Err(err),
+        };
       }
-      let wait_time = time::Duration::from_secs((attempt + 1) * 2);
-      thread::sleep(wait_time);
+      Err(err) => return Err(err),
+    };
+    if let (StatusCode::NOT_MODIFIED, Some(entry)) = (response.status(), entry) {
+      cache.stats.revalidations.fetch_add(1, Ordering::Relaxed);
+      return Ok(to_response(cache.refresh(url, entry, response.headers()).await));
     }
-    return Err(ScrapeError::Network);
+    cache.stats.misses.fetch_add(1, Ordering::Relaxed);
+    if response.status() != StatusCode::OK {
+      return Ok(response);
+    }
+
+    let headers = response.headers().clone();
+    let body = response.bytes().await.map_err(|_| ScrapeError::Network)?;
+    let entry = CacheEntry {
+      headers,
+      body: body.to_vec(),
+      stored_at: SystemTime::now(),
+    };
+    if let Err(err) = cache.store(url, &entry).await {
+      println!("Could not cache {}: {}", url, err);
+    }
+    Ok(to_response(entry))
+  }
+
+  /// Requests only the first `num_bytes` bytes of a resource. Servers that do
+  /// not support ranges answer with the full resource, so callers should stop
+  /// reading the body once they have what they need.
+  async fn get_partial(&self, url: &str, num_bytes: u64) -> ScrapeResult<Response> {
+    let mut headers = HeaderMap::new();
+    let range = format!("bytes=0-{}", num_bytes.saturating_sub(1));
+    headers.insert(RANGE, HeaderValue::from_str(&range).unwrap());
+    self.request(Method::GET, url, headers, false).await
+  }
+
+  /// Requests a resource from byte `offset` on, bypassing the HTTP cache. With
+  /// an `if_range` validator the server only answers with the rest of the
+  /// resource if it still matches, and with the whole resource otherwise. The
+  /// body is not bound by the request timeout, so readers have to time out
+  /// between chunks themselves.
+  async fn get_from(&self, url: &str, offset: u64, if_range: Option<&str>) -> ScrapeResult<Response> {
+    let mut headers = HeaderMap::new();
+    if offset > 0 {
+      let range = format!("bytes={}-", offset);
+      headers.insert(RANGE, HeaderValue::from_str(&range).unwrap());
+      if let Some(validator) = if_range.and_then(|validator| HeaderValue::from_str(validator).ok()) {
+        headers.insert(IF_RANGE, validator);
+      }
+    }
+    self.request(Method::GET, url, headers, true).await
+  }
+
+  /// Sends a request if the site's robots.txt allows it, retrying server
+  /// errors, rate limiting and transport errors according to the retry policy.
+  /// Requests to the same host are spaced out by the host's delay, and count
+  /// against the host's concurrency until the returned response is dropped,
+  /// so that streaming its body keeps the host's slot. Responses with an error
+  /// status are returned as `ScrapeError::Status`. For `is_streamed`
+  /// requests, the request timeout only applies until the response arrives.
+  async fn request(
+    &self,
+    method: Method,
+    url: &str,
+    headers: HeaderMap,
+    is_streamed: bool,
+  ) -> ScrapeResult<Response> {
+    let parsed_url = Url::parse(url).map_err(|_| ScrapeError::Parsing)?;
+    let host = parsed_url.host_str().ok_or(ScrapeError::Parsing)?;
+    let policy = RetryPolicy {
+      max_attempts: self
+        .config
+        .host_settings(host)
+        .and_then(|settings| settings.max_attempts)
+        .unwrap_or(self.retry_policy.max_attempts),
+      ..self.retry_policy.clone()
+    };
+
+    let mut error = ScrapeError::Network;
+    for attempt in 0..policy.max_attempts {
+      let permit = match self.schedule(&parsed_url).await {
+        Ok(permit) => permit,
+        // The robots.txt could not be fetched, which is retried like the
+        // request itself.
+        Err(err @ ScrapeError::Network) | Err(err @ ScrapeError::Status(_)) => {
+          error = err;
+          if attempt + 1 < policy.max_attempts {
+            tokio::time::sleep(policy.backoff(attempt)).await;
+          }
+          continue;
+        }
+        Err(err) => return Err(err),
+      };
+      let request = self.prepare(method.clone(), &parsed_url).headers(headers.clone());
+      let result = match (is_streamed, request.build()) {
+        (true, Ok(mut request)) => {
+          *request.timeout_mut() = None;
+          let response = self.client.execute(request);
+          match tokio::time::timeout(self.config.request_timeout(host), response).await {
+            Ok(result) => result.map_err(|_| ()),
+            Err(_) => Err(()),
+          }
+        }
+        (false, Ok(request)) => self.client.execute(request).await.map_err(|_| ()),
+        (_, Err(_)) => return Err(ScrapeError::Parsing),
+      };
+      let retry_delay = match result {
+        Ok(mut response) => {
+          let status = response.status();
+          match policy.classify(status) {
+            RequestOutcome::Success => {
+              response.extensions_mut().insert(permit);
+              return Ok(response);
+            }
+            RequestOutcome::Fail => return Err(ScrapeError::Status(status.as_u16())),
+            RequestOutcome::Retry => {
+              error = ScrapeError::Status(status.as_u16());
+              match policy.delay(attempt, response.headers()) {
+                Some(retry_delay) => retry_delay,
+                None => break,
+              }
+            }
+          }
+        }
+        Err(_) => {
+          error = ScrapeError::Network;
+          policy.backoff(attempt)
+        }
+      };
+      drop(permit);
+      if attempt + 1 < policy.max_attempts {
+        tokio::time::sleep(retry_delay).await;
+      }
+    }
+    Err(error)
+  }
+
+  /// Waits until the URL's host may be sent another request, spacing requests
+  /// out by the host's delay or the crawl delay of its robots.txt, whichever
+  /// is longer. The request counts against the host's concurrency until the
+  /// returned permit is dropped. URLs the robots.txt does not allow are
+  /// rejected with `ScrapeError::Disallowed`, while a robots.txt that could
+  /// not be fetched is returned as the temporary error of fetching it.
+  async fn schedule(&self, url: &Url) -> ScrapeResult<OwnedSemaphorePermit> {
+    let host = url.host_str().ok_or(ScrapeError::Parsing)?;
+    let robots = self.get_robots(url).await?;
+    let path = match url.query() {
+      Some(query) => format!("{}?{}", url.path(), query),
+      None => String::from(url.path()),
+    };
+    if !robots.is_allowed(ROBOTS_AGENT, &path) {
+      return Err(ScrapeError::Disallowed);
+    }
+    let delay = match robots.crawl_delay(ROBOTS_AGENT) {
+      Some(crawl_delay) => crawl_delay.max(self.config.host_delay(host)),
+      None => self.config.host_delay(host),
+    };
+    Ok(
+      self
+        .scheduler
+        .acquire(host, delay, self.config.host_concurrency(host))
+        .await,
+    )
+  }
+
+  /// Builds a request with the timeout configured for the URL's host.
+  fn prepare(&self, method: Method, url: &Url) -> RequestBuilder {
+    let timeout = self.config.request_timeout(url.host_str().unwrap_or(""));
+    self.client.request(method, url.clone()).timeout(timeout)
+  }
+
+  /// Gets the robots.txt of the URL's origin, fetching it if it is not cached
+  /// or expired. Missing files allow everything. Transport and server errors
+  /// are returned as `ScrapeError::Network` and `ScrapeError::Status` without
+  /// caching anything, so that callers retry them, and the site's resources
+  /// are not requested until the file could be fetched.
+  async fn get_robots(&self, url: &Url) -> ScrapeResult<RobotsTxt> {
+    let origin = url.origin().ascii_serialization();
+    if let Some((robots, expiry)) = self.robots.lock().unwrap().get(&origin) {
+      if *expiry > Instant::now() {
+        return Ok(robots.clone());
+      }
+    }
+
+    let host = url.host_str().unwrap_or("");
+    let permit = self
+      .scheduler
+      .acquire(host, self.config.host_delay(host), self.config.host_concurrency(host))
+      .await;
+    let robots_url = url.join("/robots.txt").map_err(|_| ScrapeError::Parsing)?;
+    let response = self
+      .prepare(Method::GET, &robots_url)
+      .send()
+      .await
+      .map_err(|_| ScrapeError::Network)?;
+    let status = response.status();
+    let robots = match self.retry_policy.classify(status) {
+      RequestOutcome::Success => {
+        let bytes = response.bytes().await.map_err(|_| ScrapeError::Network)?;
+        let bytes = &bytes[..bytes.len().min(MAX_ROBOTS_SIZE)];
+        RobotsTxt::parse(&String::from_utf8_lossy(bytes))
+      }
+      RequestOutcome::Fail => RobotsTxt::allow_all(),
+      RequestOutcome::Retry => return Err(ScrapeError::Status(status.as_u16())),
+    };
+    drop(permit);
+    self
+      .robots
+      .lock()
+      .unwrap()
+      .insert(origin, (robots.clone(), Instant::now() + ROBOTS_TTL));
+    Ok(robots)
   }
 }
 
+/// Turns a cached response back into a response as returned by the client.
+fn to_response(entry: CacheEntry) -> Response {
+  let mut response = http::Response::new(entry.body);
+  *response.headers_mut() = entry.headers;
+  Response::from(response)
+}
+
 #[tokio::main] // By default, tokio_postgres uses the tokio crate as its runtime.
-async fn main() -> () {
+async fn main() {
   // Connect to the database.
-  let (client, connection) = tokio_postgres::connect(
+  let (mut client, connection) = tokio_postgres::connect(
     "host=localhost user=postgres password=admin dbname=bpod",
     NoTls,
   )
@@ -78,56 +356,513 @@ async fn main() -> () {
     }
   });
 
-  client
-    .execute(
-      "CREATE TABLE IF NOT EXISTS pictures (
-                id SERIAL PRIMARY KEY,
-                date DATE NOT NULL,
-                img_url VARCHAR(2048) NOT NULL,
-                title TEXT NOT NULL,
-                description TEXT NOT NULL,
-                meta TEXT NOT NULL
-            );",
-      &[],
-    )
-    .await
-    .unwrap();
+  database::create_tables(&client).await.unwrap();
+
+  let args: Vec<String> = env::args().collect();
+  match args.get(1).map(|arg| arg.as_str()) {
+    None | Some("scrape") => scrape(&mut client).await,
+    Some("backlinks") => match args.get(2) {
+      Some(date) => print_backlinks(&client, date).await,
+      None => eprintln!("Usage: backend backlinks <YYYY-MM-DD>"),
+    },
+    Some("check-links") => {
+      let rewrite = args.iter().any(|arg| arg == "--rewrite");
+      let every = args
+        .iter()
+        .position(|arg| arg == "--every")
+        .map(|i| args.get(i + 1).and_then(|hours| hours.parse::<u64>().ok()));
+      match every {
+        Some(None) => eprintln!("Usage: backend check-links [--rewrite] [--every <hours>]"),
+        Some(Some(hours)) => loop {
+          check_links(&client, rewrite).await;
+          tokio::time::sleep(time::Duration::from_secs(hours * 60 * 60)).await;
+        },
+        None => check_links(&client, rewrite).await,
+      }
+    }
+    Some("repair-report") => print_repair_report(&client).await,
+    Some("lint") => lint(&client).await,
+    Some("verify-originals") => verify_originals(&client).await,
+    Some("thumbnails") => {
+      let force = args.iter().any(|arg| arg == "--force");
+      rebuild_thumbnails(&client, force).await
+    }
+    Some("crop") => match (args.get(2), parse_crop_rect(&args[args.len().min(3)..])) {
+      (Some(date), Ok(rect)) => override_crop(&client, date, rect).await,
+      _ => eprintln!("Usage: backend crop <YYYY-MM-DD> (<x> <y> <size> | auto)"),
+    },
+    Some(command) => eprintln!(
+      "Unknown command '{}', expected one of: scrape, backlinks, check-links, repair-report, lint, verify-originals, thumbnails, crop",
+      command
+    ),
+  }
+}
+
+/// What the background tasks processing the images of APODs share.
+struct ImageContext {
+  client: Arc<APODRequestClient>,
+  license_policy: LicensePolicy,
+  original_store: OriginalStore,
+  rendition_set: RenditionSet,
+  pool: WorkerPool,
+}
 
-  let last_date = Utc.ymd(1996, 1, 1);
-  let mut counter = Utc::today();
-  let reqwest_client = APODRequestClient::new();
+/// The outcome of processing the images of one APOD.
+struct ImageResults {
+  date: String,
+  picture_id: i32,
+  thumbnail: ScrapeResult<(Vec<Rendition>, Crop)>,
+  /// The URL of the original and the outcome of storing it, if it was not
+  /// stored before.
+  original: Option<(String, ScrapeResult<Original>)>,
+}
+
+async fn scrape(client: &mut tokio_postgres::Client) {
+  let last_date = NaiveDate::from_ymd_opt(1996, 1, 1).unwrap();
+  let mut counter = Utc::now().date_naive();
+  let client_config = ClientConfig::from_env().unwrap_or_else(|err| panic!("{}", err));
+  let reqwest_client = APODRequestClient::new(client_config).unwrap_or_else(|err| panic!("{}", err));
+  let context = Arc::new(ImageContext {
+    client: Arc::new(reqwest_client),
+    license_policy: LicensePolicy::from_env().unwrap_or_else(|err| panic!("{}", err)),
+    original_store: OriginalStore::from_env(),
+    rendition_set: RenditionSet::from_env().unwrap_or_else(|err| panic!("{}", err)),
+    pool: WorkerPool::from_env().unwrap_or_else(|err| panic!("{}", err)),
+  });
+  // Images are processed in the background while the next pages are scraped.
+  // Twice as many APODs as workers are in flight, so that workers do not idle
+  // while images are downloaded, but scraping waits once they fall behind.
+  let max_pending = context.pool.size() * 2;
+  let mut pending = JoinSet::new();
 
   while counter >= last_date {
     let date_str = format!("{}", counter.format("%Y-%m-%d"));
-    let apod = match get_apod_data(date_str.as_str(), &reqwest_client).await {
+    let apod = match get_apod_data(date_str.as_str(), &context.client).await {
       Ok(Some(apod)) => apod,
       Ok(None) => {
-        counter = counter - chrono::Duration::days(1);
+        counter -= chrono::Duration::days(1);
         continue;
       }
-      Err(err) => panic!(err),
+      Err(err) => panic!("{}", err),
     };
 
-    match get_apod_thumbnail(&apod, &reqwest_client).await {
-      Ok(_) => (),
-      Err(err) => println!("Could not get thumbnail: {}", err),
-    }
-
+    let names_with_role = |role: &str| {
+      apod
+        .credits
+        .iter()
+        .filter(|credit| credit.role == role)
+        .map(|credit| credit.contributor.name.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ")
+    };
     println!(
-      // "Date: {}, Image URL: {}, Title: {}, Description: {}, Credit: {}, Image editor: {}, Text author: {}, Copyright: {}, License: {}",
-      "Date: {}, Image URL: {}, Title: {}",
+      "Date: {}, Image URL: {}, Full-size image URL: {}, Media: {}, Title: {}, Credit: {}, Image editor: {}, Text author: {}, Copyright: {}, License: {}",
       apod.date,
-      apod.img_url,
+      apod.primary_media().img.url,
+      apod
+        .primary_media()
+        .hires_img
+        .as_ref()
+        .map_or("-", |img| img.url.as_str()),
+      apod.media.len(),
       apod.title,
       // apod.description,
+      names_with_role("Image Credit"),
+      names_with_role("Image Editor"),
+      names_with_role("Text"),
+      apod
+        .copyright_holders()
+        .iter()
+        .map(|contributor| contributor.name.as_str())
+        .collect::<Vec<&str>>()
+        .join(", "),
+      apod
+        .license
+        .variant
+        .as_deref()
+        .unwrap_or_else(|| apod.license.class.as_str()),
+    );
+    for warning in apod.warnings.iter() {
+      println!("Warning: {}", warning);
+    }
+    if !apod.repairs.is_empty() {
+      println!("Repaired {} HTML issues", apod.repairs.len());
+    }
+    let picture_id = apod.save(client).await.unwrap();
+    let stored_crop = database::get_crop(client, &apod.date).await.unwrap();
+    let original_url = get_missing_original_url(client, &apod, &context.original_store).await;
+    pending.spawn(process_images(
+      context.clone(),
+      apod,
+      picture_id,
+      stored_crop,
+      original_url,
+    ));
+    while pending.len() >= max_pending {
+      save_image_results(client, pending.join_next().await.unwrap().unwrap()).await;
+    }
+
+    counter -= chrono::Duration::days(1);
+  }
+  while let Some(results) = pending.join_next().await {
+    save_image_results(client, results.unwrap()).await;
+  }
+
+  if let Some(cache) = context.client.cache.as_ref() {
+    println!("HTTP cache: {}", cache.stats);
+  }
+}
+
+/// Generates the renditions of an APOD and stores its original, if needed.
+async fn process_images(
+  context: Arc<ImageContext>,
+  apod: apod::APOD,
+  picture_id: i32,
+  stored_crop: Option<Crop>,
+  original_url: Option<String>,
+) -> ImageResults {
+  let thumbnail = get_apod_thumbnail(
+    &apod,
+    &context.client,
+    &context.license_policy,
+    &context.rendition_set,
+    stored_crop,
+    &context.pool,
+  )
+  .await;
+  let original = match original_url {
+    Some(url) => {
+      let original = get_apod_original(
+        &apod,
+        &context.client,
+        &context.license_policy,
+        &context.original_store,
+        &context.pool,
+      )
+      .await;
+      Some((url, original))
+    }
+    None => None,
+  };
+  ImageResults {
+    date: apod.date,
+    picture_id,
+    thumbnail,
+    original,
+  }
+}
+
+/// Records the renditions of an APOD and deletes the files of renditions that
+/// are not generated anymore, like shapes or formats removed from the set.
+async fn save_renditions(client: &tokio_postgres::Client, picture_id: i32, renditions: &[Rendition]) {
+  let replaced = database::save_renditions(client, picture_id, renditions)
+    .await
+    .unwrap();
+  for file_name in replaced {
+    if let Err(err) = remove_rendition(&file_name) {
+      println!("Could not remove {}: {}", file_name, err);
+    }
+  }
+}
+
+/// Records the renditions and the original of an APOD. Renditions written by
+/// earlier scrapes stay recorded if generating them failed this time, unless
+/// the license does not permit them anymore.
+async fn save_image_results(client: &tokio_postgres::Client, results: ImageResults) {
+  match results.thumbnail {
+    Ok((renditions, crop)) => {
+      save_renditions(client, results.picture_id, &renditions).await;
+      database::save_crop(client, results.picture_id, &crop)
+        .await
+        .unwrap();
+    }
+    Err(ScrapeError::Unlicensed) => save_renditions(client, results.picture_id, &[]).await,
+    Err(err) => println!("Could not get thumbnail of {}: {}", results.date, err),
+  }
+  match results.original {
+    Some((url, Ok(original))) => {
+      database::save_original(client, &url, &original).await.unwrap();
+      println!(
+        "Stored original of {} as {} ({}x{} {}, {} bytes)",
+        results.date, original.sha256, original.width, original.height, original.format, original.byte_size
+      );
+    }
+    Some((_, Err(err))) => println!("Could not store original of {}: {}", results.date, err),
+    None => (),
+  }
+}
+
+/// The URL of the full-size image of the APOD, unless it is in the originals
+/// store already.
+async fn get_missing_original_url(
+  client: &tokio_postgres::Client,
+  apod: &apod::APOD,
+  store: &OriginalStore,
+) -> Option<String> {
+  let url = get_original_url(apod)?;
+  let sha256 = database::get_original_sha256(client, url).await.unwrap();
+  match sha256.is_some_and(|sha256| store.contains(&sha256)) {
+    true => None,
+    false => Some(String::from(url)),
+  }
+}
+
+async fn print_backlinks(client: &tokio_postgres::Client, date: &str) {
+  let dates = database::get_referencing_dates(client, date).await.unwrap();
+  if dates.is_empty() {
+    println!("No APOD references {}", date);
+  }
+  for referencing_date in dates {
+    println!("{}", referencing_date);
+  }
+}
+
+/// Prints how often each normalization rule repaired the HTML of the stored
+/// APODs, with an example of what it changed.
+async fn print_repair_report(client: &tokio_postgres::Client) {
+  let counts = database::get_repair_counts(client).await.unwrap();
+  if counts.is_empty() {
+    println!("No repairs recorded");
+  }
+  for count in counts {
+    println!(
+      "{}: {} repairs in {} APODs, e.g. {:?} -> {:?}",
+      count.rule, count.hits, count.num_pictures, count.example.0, count.example.1
     );
-    // apod.save(client).await.unwrap();
+  }
+}
 
-    let half_sec = time::Duration::from_millis(500);
-    thread::sleep(half_sec);
+/// Lints the stored HTML of all APODs and prints every violation found.
+async fn lint(client: &tokio_postgres::Client) {
+  let texts = database::get_html_texts(client).await.unwrap();
+  let mut num_violations = 0;
+  for (date, html) in texts.iter() {
+    let sections = [
+      ("title", &html.title),
+      ("explanation", &html.description),
+      ("meta", &html.meta),
+    ];
+    for (section, text) in sections.iter() {
+      for violation in lint_html(text) {
+        println!("{} {}: {}", date, section, violation);
+        num_violations += 1;
+      }
+    }
+  }
+  println!("{} violations in {} APODs", num_violations, texts.len());
+}
 
-    counter = counter - chrono::Duration::days(1);
+/// Rebuilds the renditions of all APODs from their stored originals without
+/// network access. APODs whose renditions were built with the current recipe
+/// and still exist are skipped, unless `force` is set. Renditions of APODs
+/// whose license does not permit thumbnails anymore are deleted. Originals
+/// that can not be decoded are listed at the end, other failures like
+/// renditions that can not be encoded are reported as they happen.
+async fn rebuild_thumbnails(client: &tokio_postgres::Client, force: bool) {
+  let store = OriginalStore::from_env();
+  let license_policy = LicensePolicy::from_env().unwrap_or_else(|err| panic!("{}", err));
+  let rendition_set = Arc::new(RenditionSet::from_env().unwrap_or_else(|err| panic!("{}", err)));
+  let pool = WorkerPool::from_env().unwrap_or_else(|err| panic!("{}", err));
+  let sources = database::get_thumbnail_sources(client).await.unwrap();
+  let max_pending = pool.size() * 2;
+  let mut pending = JoinSet::new();
+  let mut num_skipped = 0;
+  let mut num_rebuilt = 0;
+  let mut num_removed = 0;
+  let mut num_failed = 0;
+  let mut undecodable = Vec::new();
+  let mut handle_result = |source: ThumbnailSource, result: ScrapeResult<(Vec<Rendition>, Crop)>| match result {
+    Ok(thumbnail) => {
+      num_rebuilt += 1;
+      Some((source, thumbnail))
+    }
+    Err(ScrapeError::Image) => {
+      undecodable.push(source);
+      None
+    }
+    Err(err) => {
+      num_failed += 1;
+      println!("Could not rebuild renditions of {}: {}", source.date, err);
+      None
+    }
+  };
+
+  for source in sources {
+    if !license_policy.allows_thumbnail(&source.license) {
+      if !source.file_names.is_empty() {
+        save_renditions(client, source.picture_id, &[]).await;
+        println!("Removed renditions of {}, as its license does not permit them", source.date);
+        num_removed += 1;
+      }
+      continue;
+    }
+    let stored_crop = database::get_crop(client, &source.date).await.unwrap();
+    let recipe = rendition_set.recipe(&source.original_sha256, stored_crop.as_ref());
+    let is_up_to_date = !source.recipes.is_empty()
+      && source.recipes.iter().all(|stored_recipe| *stored_recipe == recipe)
+      && source.file_names.iter().all(|file_name| rendition_exists(file_name));
+    if is_up_to_date && !force {
+      num_skipped += 1;
+      continue;
+    }
+
+    let path = store.path(&source.original_sha256);
+    let rendition_set = rendition_set.clone();
+    let pool = pool.clone();
+    pending.spawn(async move {
+      let date = source.date.clone();
+      let result = rebuild_thumbnail(path, date, &rendition_set, stored_crop, recipe, &pool).await;
+      (source, result)
+    });
+    while pending.len() >= max_pending {
+      let (source, result) = pending.join_next().await.unwrap().unwrap();
+      if let Some((source, thumbnail)) = handle_result(source, result) {
+        save_rebuilt_thumbnail(client, &source, thumbnail).await;
+      }
+    }
+  }
+  while let Some(finished) = pending.join_next().await {
+    let (source, result) = finished.unwrap();
+    if let Some((source, thumbnail)) = handle_result(source, result) {
+      save_rebuilt_thumbnail(client, &source, thumbnail).await;
+    }
+  }
+
+  println!(
+    "Rebuilt renditions of {} APODs, skipped {} up to date, removed {} unlicensed, {} failed",
+    num_rebuilt, num_skipped, num_removed, num_failed
+  );
+  if !undecodable.is_empty() {
+    println!("{} originals could not be decoded:", undecodable.len());
+  }
+  for source in undecodable {
+    println!("{} {}", source.date, source.original_sha256);
+  }
+}
+
+async fn save_rebuilt_thumbnail(
+  client: &tokio_postgres::Client,
+  source: &ThumbnailSource,
+  (renditions, crop): (Vec<Rendition>, Crop),
+) {
+  save_renditions(client, source.picture_id, &renditions).await;
+  database::save_crop(client, source.picture_id, &crop)
+    .await
+    .unwrap();
+  println!("Rebuilt {} renditions of {}", renditions.len(), source.date);
+}
+
+/// Parses the arguments of the crop command, which are either `auto` or the
+/// position and size of the square.
+fn parse_crop_rect(args: &[String]) -> Result<Option<CropRect>, ()> {
+  match args {
+    [auto] if auto == "auto" => Ok(None),
+    [x, y, size] => Ok(Some(CropRect {
+      x: x.parse().map_err(|_| ())?,
+      y: y.parse().map_err(|_| ())?,
+      size: size.parse().map_err(|_| ())?,
+    })),
+    _ => Err(()),
+  }
+}
+
+/// Sets or clears the manual crop of an APOD, which is used from the next time
+/// its renditions are generated.
+async fn override_crop(client: &tokio_postgres::Client, date: &str, rect: Option<CropRect>) {
+  let num_updated = database::override_crop(client, date, rect).await.unwrap();
+  match (num_updated, rect) {
+    (0, _) => println!("{} has no thumbnail yet or the crop does not fit its image", date),
+    (_, Some(_)) => println!("Set manual crop of {}", date),
+    (_, None) => println!("Crop of {} is chosen automatically again", date),
+  }
+}
+
+/// Checks that every stored original still has the hash it was stored under
+/// and records the outcome.
+async fn verify_originals(client: &tokio_postgres::Client) {
+  let store = OriginalStore::from_env();
+  let hashes = database::get_original_hashes(client).await.unwrap();
+  let mut num_damaged = 0;
+  for sha256 in hashes.iter() {
+    let is_intact = match store.verify(sha256) {
+      Ok(true) => true,
+      Ok(false) => {
+        println!("Corrupt original {}", sha256);
+        false
+      }
+      Err(err) => {
+        println!("Could not read original {}: {}", sha256, err);
+        false
+      }
+    };
+    if !is_intact {
+      num_damaged += 1;
+    }
+    database::save_verification(client, sha256, is_intact)
+      .await
+      .unwrap();
   }
+  println!("{} of {} originals are damaged or missing", num_damaged, hashes.len());
+}
 
-  ()
+/// Checks all links that are due and records the outcome. With `rewrite`,
+/// links that stayed dead are replaced by an archived copy, and archived links
+/// that are alive again are restored.
+async fn check_links(client: &tokio_postgres::Client, rewrite: bool) {
+  let client_config = ClientConfig::from_env().unwrap_or_else(|err| panic!("{}", err));
+  let reqwest_client = APODRequestClient::new(client_config).unwrap_or_else(|err| panic!("{}", err));
+  let checker = LinkChecker::new(Arc::new(reqwest_client));
+  let resolver = WaybackResolver::default();
+  loop {
+    let due_links =
+      database::get_links_due_for_check(client, link_check::RECHECK_AFTER_DAYS, 100)
+        .await
+        .unwrap();
+    if due_links.is_empty() {
+      return;
+    }
+    for (url, date) in due_links {
+      let check = checker.check(&url).await;
+      database::save_link_check(client, &url, &check).await.unwrap();
+      println!(
+        "{} {} {}",
+        check.health.as_str(),
+        check
+          .status_code
+          .map_or_else(|| String::from("-"), |code| code.to_string()),
+        url
+      );
+      if !rewrite {
+        continue;
+      }
+
+      let archive_url = database::get_archive_url(client, &url).await.unwrap();
+      match (check.health, archive_url) {
+        (LinkHealth::Alive, Some(archive_url)) => {
+          database::restore_link(client, &url, &archive_url)
+            .await
+            .unwrap();
+          println!("Restored {}", url);
+        }
+        (LinkHealth::Dead, None) => {
+          let is_dead = database::is_link_dead(client, &url, link_check::DEAD_AFTER_CHECKS)
+            .await
+            .unwrap();
+          if !is_dead {
+            continue;
+          }
+          match resolver.resolve(checker.client(), &url, &date).await {
+            Some(archive_url) => {
+              database::archive_link(client, &url, &archive_url)
+                .await
+                .unwrap();
+              println!("Archived {} as {}", url, archive_url);
+            }
+            None => println!("No archived copy of {}", url),
+          }
+        }
+        _ => (),
+      }
+    }
+  }
 }
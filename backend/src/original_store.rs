@@ -0,0 +1,152 @@
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// A full-size image kept in the originals store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Original {
+  /// Hex encoded SHA-256 hash of the file, which is also its key in the store.
+  pub sha256: String,
+  /// Image format like `jpeg` or `png`.
+  pub format: String,
+  pub width: u32,
+  pub height: u32,
+  pub byte_size: u64,
+}
+
+/// Keeps full-size images as private archive copies, named after the SHA-256
+/// hash of their contents so that every image is stored once no matter how
+/// many URLs point to it.
+#[derive(Debug, Clone)]
+pub struct OriginalStore {
+  directory: PathBuf,
+}
+
+impl OriginalStore {
+  pub fn new(directory: PathBuf) -> OriginalStore {
+    OriginalStore { directory }
+  }
+
+  /// Uses the directory in `BPOD_ORIGINALS_DIR`, falling back to
+  /// `~/bpod/originals`.
+  pub fn from_env() -> OriginalStore {
+    let directory = env::var("BPOD_ORIGINALS_DIR")
+      .ok()
+      .filter(|directory| !directory.trim().is_empty())
+      .map(PathBuf::from)
+      .unwrap_or_else(|| dirs::home_dir().unwrap().join("bpod").join("originals"));
+    OriginalStore::new(directory)
+  }
+
+  /// Where an image is downloaded to before it is added to the store.
+  pub fn incoming_path(&self, file_name: &str) -> PathBuf {
+    self.directory.join("incoming").join(file_name)
+  }
+
+  /// Where the image with the given hash is stored, in subdirectories by the
+  /// first two hex digits to keep directories small.
+  pub fn path(&self, sha256: &str) -> PathBuf {
+    self.directory.join(&sha256[..2]).join(sha256)
+  }
+
+  pub fn contains(&self, sha256: &str) -> bool {
+    self.path(sha256).is_file()
+  }
+
+  /// Moves a file into the store and returns its hash. A file that is already
+  /// stored is removed instead.
+  pub fn add(&self, file: &Path) -> io::Result<String> {
+    let sha256 = hash_file(file)?;
+    let path = self.path(&sha256);
+    if path.is_file() {
+      fs::remove_file(file)?;
+      return Ok(sha256);
+    }
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::rename(file, &path)?;
+    Ok(sha256)
+  }
+
+  /// Whether the stored file still has the hash it was stored under. Missing
+  /// files are returned as `NotFound` error.
+  pub fn verify(&self, sha256: &str) -> io::Result<bool> {
+    Ok(hash_file(&self.path(sha256))? == sha256)
+  }
+}
+
+/// Hashes a file without reading it into memory at once.
+fn hash_file(path: &Path) -> io::Result<String> {
+  let mut file = File::open(path)?;
+  let mut hasher = Sha256::new();
+  let mut buffer = vec![0; 64 * 1024];
+  loop {
+    let num_bytes = file.read(&mut buffer)?;
+    if num_bytes == 0 {
+      break;
+    }
+    hasher.update(&buffer[..num_bytes]);
+  }
+  Ok(to_hex(&hasher.finalize()))
+}
+
+/// Hex encoded SHA-256 hash of the bytes, as used for keys and file names.
+pub fn hex_sha256(bytes: &[u8]) -> String {
+  to_hex(&Sha256::digest(bytes))
+}
+
+fn to_hex(digest: &[u8]) -> String {
+  digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::temp_dir;
+
+  const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+  fn temp_store(name: &str) -> OriginalStore {
+    let directory = temp_dir("originals", name);
+    fs::create_dir_all(directory.join("incoming")).unwrap();
+    OriginalStore::new(directory)
+  }
+
+  #[test]
+  fn hashes_bytes() {
+    assert_eq!(hex_sha256(b""), EMPTY_SHA256);
+  }
+
+  #[test]
+  fn stores_files_by_hash() {
+    let store = temp_store("add");
+    let incoming = store.incoming_path("m31.jpg");
+    fs::write(&incoming, b"").unwrap();
+    assert_eq!(store.add(&incoming).unwrap(), EMPTY_SHA256);
+    assert!(!incoming.exists());
+    assert!(store.contains(EMPTY_SHA256));
+    assert_eq!(store.path(EMPTY_SHA256), store.directory.join("e3").join(EMPTY_SHA256));
+
+    let duplicate = store.incoming_path("m31-copy.jpg");
+    fs::write(&duplicate, b"").unwrap();
+    assert_eq!(store.add(&duplicate).unwrap(), EMPTY_SHA256);
+    assert!(!duplicate.exists());
+    fs::remove_dir_all(&store.directory).unwrap();
+  }
+
+  #[test]
+  fn verifies_integrity() {
+    let store = temp_store("verify");
+    let incoming = store.incoming_path("m31.jpg");
+    fs::write(&incoming, b"M31").unwrap();
+    let sha256 = store.add(&incoming).unwrap();
+    assert!(store.verify(&sha256).unwrap());
+
+    fs::write(store.path(&sha256), b"M33").unwrap();
+    assert!(!store.verify(&sha256).unwrap());
+    fs::remove_file(store.path(&sha256)).unwrap();
+    assert_eq!(store.verify(&sha256).unwrap_err().kind(), io::ErrorKind::NotFound);
+    fs::remove_dir_all(&store.directory).unwrap();
+  }
+}
@@ -0,0 +1,336 @@
+use crate::original_store::hex_sha256;
+use crate::smart_crop::{Crop, CropMode, CropRect};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageOutputFormat};
+use std::env;
+use webp::Encoder as WebPEncoder;
+
+const JPEG_QUALITY: u8 = 85;
+const WEBP_QUALITY: f32 = 80.0;
+/// Version of the rendering and cropping code, to be increased whenever it
+/// changes the output so that renditions get rebuilt.
+const RECIPE_VERSION: u32 = 1;
+
+/// How a rendition is cut from the source image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenditionShape {
+  /// A square of the given side length, cut from the crop rectangle.
+  Square(u32),
+  /// The whole image scaled to the given width.
+  Width(u32),
+}
+
+impl RenditionShape {
+  /// Parses shapes like `square:250` or `width:640`.
+  fn parse(shape: &str) -> Result<RenditionShape, String> {
+    let mut parts = shape.trim().splitn(2, ':');
+    let kind = parts.next().unwrap_or("");
+    let size = parts
+      .next()
+      .and_then(|size| size.trim().parse::<u32>().ok())
+      .filter(|size| *size > 0)
+      .ok_or(format!("Invalid rendition size in '{}'", shape))?;
+    match kind {
+      "square" => Ok(RenditionShape::Square(size)),
+      "width" => Ok(RenditionShape::Width(size)),
+      other => Err(format!("Unknown rendition shape '{}'", other)),
+    }
+  }
+
+  pub fn kind(&self) -> &'static str {
+    match self {
+      RenditionShape::Square(_) => "square",
+      RenditionShape::Width(_) => "width",
+    }
+  }
+
+  pub fn size(&self) -> u32 {
+    match self {
+      RenditionShape::Square(size) | RenditionShape::Width(size) => *size,
+    }
+  }
+
+  /// Short name used in file names, like `s250` or `w640`.
+  fn name(&self) -> String {
+    match self {
+      RenditionShape::Square(size) => format!("s{}", size),
+      RenditionShape::Width(size) => format!("w{}", size),
+    }
+  }
+}
+
+/// File formats renditions are encoded in. WebP and JPEG renditions are lossy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenditionFormat {
+  WebP,
+  Jpeg,
+  Png,
+}
+
+impl RenditionFormat {
+  fn parse(format: &str) -> Result<RenditionFormat, String> {
+    match format.trim() {
+      "webp" => Ok(RenditionFormat::WebP),
+      "jpeg" => Ok(RenditionFormat::Jpeg),
+      "png" => Ok(RenditionFormat::Png),
+      other => Err(format!("Unknown rendition format '{}'", other)),
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      RenditionFormat::WebP => "webp",
+      RenditionFormat::Jpeg => "jpeg",
+      RenditionFormat::Png => "png",
+    }
+  }
+
+  pub fn extension(&self) -> &'static str {
+    match self {
+      RenditionFormat::WebP => "webp",
+      RenditionFormat::Jpeg => "jpg",
+      RenditionFormat::Png => "png",
+    }
+  }
+}
+
+/// A rendition generated for an APOD.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendition {
+  pub shape: RenditionShape,
+  pub format: RenditionFormat,
+  pub width: u32,
+  pub height: u32,
+  pub byte_size: u64,
+  pub file_name: String,
+  /// Hash of everything the rendition was generated from, for renditions
+  /// built from a stored original.
+  pub recipe: Option<String>,
+}
+
+/// The renditions generated for every APOD: each shape in each format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenditionSet {
+  pub shapes: Vec<RenditionShape>,
+  pub formats: Vec<RenditionFormat>,
+  pub crop_mode: CropMode,
+}
+
+impl Default for RenditionSet {
+  fn default() -> Self {
+    Self {
+      shapes: vec![
+        RenditionShape::Square(250),
+        RenditionShape::Width(320),
+        RenditionShape::Width(640),
+        RenditionShape::Width(1280),
+      ],
+      formats: vec![RenditionFormat::WebP, RenditionFormat::Jpeg, RenditionFormat::Png],
+      crop_mode: CropMode::Smart,
+    }
+  }
+}
+
+impl RenditionSet {
+  /// Reads comma-separated shapes like `square:250,width:640` from
+  /// `BPOD_RENDITIONS` and formats like `webp,jpeg` from
+  /// `BPOD_RENDITION_FORMATS`, and `center` or `smart` from `BPOD_CROP_MODE`,
+  /// using the default for unset variables.
+  pub fn from_env() -> Result<Self, String> {
+    let mut set = Self::default();
+    if let Ok(spec) = env::var("BPOD_RENDITIONS") {
+      set.shapes = parse_list(&spec, RenditionShape::parse)?;
+    }
+    if let Ok(spec) = env::var("BPOD_RENDITION_FORMATS") {
+      set.formats = parse_list(&spec, RenditionFormat::parse)?;
+    }
+    if let Ok(mode) = env::var("BPOD_CROP_MODE") {
+      set.crop_mode = CropMode::parse(&mode)?;
+    }
+    Ok(set)
+  }
+
+  /// The shapes that fit the image. Widths larger than the image are skipped
+  /// instead of upscaling it.
+  pub fn shapes_for(&self, img: &DynamicImage) -> Vec<RenditionShape> {
+    self
+      .shapes
+      .iter()
+      .filter(|shape| match shape {
+        RenditionShape::Square(_) => true,
+        RenditionShape::Width(width) => *width <= img.width(),
+      })
+      .copied()
+      .collect()
+  }
+
+  /// Hashes everything that determines the renditions of an original: the
+  /// original itself, the set, the encoder settings and a manual crop. Manual
+  /// crops keep the coordinates they were set in, so their hash does not
+  /// depend on the image they were last applied to.
+  pub fn recipe(&self, original_sha256: &str, stored_crop: Option<&Crop>) -> String {
+    let shapes: Vec<String> = self.shapes.iter().map(RenditionShape::name).collect();
+    let formats: Vec<&str> = self.formats.iter().map(RenditionFormat::as_str).collect();
+    let crop = match stored_crop.filter(|crop| crop.is_manual) {
+      Some(crop) => format!(
+        "{},{},{}@{}x{}",
+        crop.rect.x, crop.rect.y, crop.rect.size, crop.source_width, crop.source_height
+      ),
+      None => String::from(match self.crop_mode {
+        CropMode::Center => "center",
+        CropMode::Smart => "smart",
+      }),
+    };
+    let recipe = format!(
+      "{}|{}|{}|{}|q{}|w{}|{}",
+      RECIPE_VERSION,
+      original_sha256,
+      shapes.join(","),
+      formats.join(","),
+      JPEG_QUALITY,
+      WEBP_QUALITY,
+      crop
+    );
+    hex_sha256(recipe.as_bytes())
+  }
+}
+
+fn parse_list<T>(spec: &str, parse: fn(&str) -> Result<T, String>) -> Result<Vec<T>, String> {
+  let items = spec
+    .split(',')
+    .filter(|item| !item.trim().is_empty())
+    .map(parse)
+    .collect::<Result<Vec<T>, String>>()?;
+  match items.is_empty() {
+    true => Err(format!("Empty rendition list '{}'", spec)),
+    false => Ok(items),
+  }
+}
+
+pub fn get_rendition_file_name(date: &str, shape: RenditionShape, format: RenditionFormat) -> String {
+  format!("{}-{}.{}", date, shape.name(), format.extension())
+}
+
+/// Scales the image to the shape. Squares are cut from the crop rectangle.
+pub fn render(img: &DynamicImage, shape: RenditionShape, crop: &CropRect) -> DynamicImage {
+  match shape {
+    RenditionShape::Square(size) => img
+      .crop_imm(crop.x, crop.y, crop.size, crop.size)
+      .resize_exact(size, size, FilterType::CatmullRom),
+    RenditionShape::Width(width) => {
+      let height = (img.height() as u64 * width as u64 + img.width() as u64 / 2) / img.width() as u64;
+      img.resize_exact(width, height.max(1) as u32, FilterType::CatmullRom)
+    }
+  }
+}
+
+pub fn encode(img: &DynamicImage, format: RenditionFormat) -> Result<Vec<u8>, String> {
+  let mut bytes = Vec::new();
+  match format {
+    RenditionFormat::WebP => {
+      let (width, height) = img.dimensions();
+      let encoded = match img.color().has_alpha() {
+        true => WebPEncoder::from_rgba(&img.to_rgba8(), width, height).encode(WEBP_QUALITY),
+        false => WebPEncoder::from_rgb(&img.to_rgb8(), width, height).encode(WEBP_QUALITY),
+      };
+      bytes.extend_from_slice(&encoded);
+    }
+    // JPEG has no alpha channel.
+    RenditionFormat::Jpeg => DynamicImage::ImageRgb8(img.to_rgb8())
+      .write_to(&mut bytes, ImageOutputFormat::Jpeg(JPEG_QUALITY))
+      .map_err(|err| err.to_string())?,
+    RenditionFormat::Png => img
+      .write_to(&mut bytes, ImageOutputFormat::Png)
+      .map_err(|err| err.to_string())?,
+  }
+  Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_rendition_lists() {
+    assert_eq!(
+      parse_list("square:100, width:320", RenditionShape::parse).unwrap(),
+      vec![RenditionShape::Square(100), RenditionShape::Width(320)]
+    );
+    assert_eq!(
+      parse_list("jpeg,webp", RenditionFormat::parse).unwrap(),
+      vec![RenditionFormat::Jpeg, RenditionFormat::WebP]
+    );
+    assert!(parse_list("circle:100", RenditionShape::parse).is_err());
+    assert!(parse_list("width:0", RenditionShape::parse).is_err());
+    assert!(parse_list("gif", RenditionFormat::parse).is_err());
+    assert!(parse_list("", RenditionFormat::parse).is_err());
+  }
+
+  #[test]
+  fn renders_shapes() {
+    let img = DynamicImage::new_rgb8(800, 600);
+    let crop = CropRect::center(800, 600);
+    assert_eq!(render(&img, RenditionShape::Square(250), &crop).dimensions(), (250, 250));
+    assert_eq!(render(&img, RenditionShape::Width(320), &crop).dimensions(), (320, 240));
+    assert_eq!(
+      RenditionSet::default().shapes_for(&img),
+      vec![
+        RenditionShape::Square(250),
+        RenditionShape::Width(320),
+        RenditionShape::Width(640)
+      ]
+    );
+    assert_eq!(
+      get_rendition_file_name("2021-01-01", RenditionShape::Width(640), RenditionFormat::Jpeg),
+      "2021-01-01-w640.jpg"
+    );
+  }
+
+  #[test]
+  fn hashes_recipes() {
+    let set = RenditionSet::default();
+    let recipe = set.recipe("e3b0c442", None);
+    assert_eq!(recipe.len(), 64);
+    assert_eq!(recipe, set.recipe("e3b0c442", None));
+    assert_ne!(recipe, set.recipe("5f3a1b2c", None));
+    let center = RenditionSet {
+      crop_mode: CropMode::Center,
+      ..RenditionSet::default()
+    };
+    assert_ne!(recipe, center.recipe("e3b0c442", None));
+
+    let mut crop = Crop {
+      rect: CropRect { x: 10, y: 0, size: 200 },
+      source_width: 600,
+      source_height: 200,
+      is_manual: false,
+    };
+    assert_eq!(recipe, set.recipe("e3b0c442", Some(&crop)));
+    crop.is_manual = true;
+    assert_ne!(recipe, set.recipe("e3b0c442", Some(&crop)));
+  }
+
+  #[test]
+  fn encodes_formats() {
+    let img = DynamicImage::new_rgba8(4, 3);
+    for (format, magic) in [
+      (RenditionFormat::WebP, &b"RIFF"[..]),
+      (RenditionFormat::Jpeg, &b"\xFF\xD8\xFF"[..]),
+      (RenditionFormat::Png, &b"\x89PNG"[..]),
+    ]
+    .iter()
+    {
+      assert!(encode(&img, *format).unwrap().starts_with(magic));
+    }
+  }
+
+  #[test]
+  fn encodes_lossy_webp() {
+    for img in [DynamicImage::new_rgb8(64, 48), DynamicImage::new_rgba8(64, 48)].iter() {
+      let bytes = encode(img, RenditionFormat::WebP).unwrap();
+      assert!(bytes.windows(4).any(|chunk| chunk == b"VP8 "));
+      assert!(!bytes.windows(4).any(|chunk| chunk == b"VP8L"));
+    }
+    assert_eq!(RenditionSet::default().formats[0], RenditionFormat::WebP);
+  }
+}
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+/// The product token BPOD looks for in `User-agent` lines.
+pub const ROBOTS_AGENT: &str = "bpod";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+  allow: bool,
+  pattern: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Group {
+  agents: Vec<String>,
+  rules: Vec<Rule>,
+  crawl_delay: Option<Duration>,
+}
+
+/// The rules of a robots.txt file as described in RFC 9309, plus the
+/// widespread `Crawl-delay` extension.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RobotsTxt {
+  groups: Vec<Group>,
+}
+
+impl RobotsTxt {
+  /// Rules for sites without robots.txt.
+  pub fn allow_all() -> RobotsTxt {
+    RobotsTxt::default()
+  }
+
+  pub fn parse(text: &str) -> RobotsTxt {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut is_agent_line_before = false;
+    for line in text.lines() {
+      let line = line.split('#').next().unwrap_or("").trim();
+      let mut parts = line.splitn(2, ':');
+      let key = parts.next().unwrap_or("").trim().to_lowercase();
+      let value = match parts.next() {
+        Some(value) => value.trim(),
+        None => continue,
+      };
+
+      if key == "user-agent" {
+        if !is_agent_line_before {
+          groups.push(Group::default());
+        }
+        groups.last_mut().unwrap().agents.push(value.to_lowercase());
+        is_agent_line_before = true;
+        continue;
+      }
+      is_agent_line_before = false;
+      let group = match groups.last_mut() {
+        Some(group) => group,
+        None => continue,
+      };
+      match key.as_str() {
+        // An empty disallow rule allows everything.
+        "allow" | "disallow" if !value.is_empty() => group.rules.push(Rule {
+          allow: key == "allow",
+          pattern: String::from(value),
+        }),
+        "crawl-delay" => {
+          group.crawl_delay = value
+            .parse::<f64>()
+            .ok()
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+            .map(Duration::from_secs_f64)
+        }
+        _ => (),
+      }
+    }
+    RobotsTxt { groups }
+  }
+
+  /// Whether `agent` may fetch `path`, which includes the query. The longest
+  /// matching rule decides, and allow rules win over disallow rules of the
+  /// same length.
+  pub fn is_allowed(&self, agent: &str, path: &str) -> bool {
+    if path == "/robots.txt" {
+      return true;
+    }
+    self
+      .get_groups(agent)
+      .iter()
+      .flat_map(|group| group.rules.iter())
+      .filter(|rule| matches_pattern(&rule.pattern, path))
+      .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+      .is_none_or(|rule| rule.allow)
+  }
+
+  pub fn crawl_delay(&self, agent: &str) -> Option<Duration> {
+    self
+      .get_groups(agent)
+      .iter()
+      .filter_map(|group| group.crawl_delay)
+      .max()
+  }
+
+  /// The groups for the agent, or the groups for all agents if no group names
+  /// the agent.
+  fn get_groups(&self, agent: &str) -> Vec<&Group> {
+    let agent = agent.to_lowercase();
+    let named: Vec<&Group> = self
+      .groups
+      .iter()
+      .filter(|group| group.agents.contains(&agent))
+      .collect();
+    if !named.is_empty() {
+      return named;
+    }
+    self
+      .groups
+      .iter()
+      .filter(|group| group.agents.iter().any(|name| name == "*"))
+      .collect()
+  }
+}
+
+/// Matches a path against a rule pattern, in which `*` stands for any
+/// sequence of characters and a trailing `$` anchors the pattern at the end.
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+  let (pattern, is_anchored) = match pattern.strip_suffix('$') {
+    Some(pattern) => (pattern, true),
+    None => (pattern, false),
+  };
+  let mut parts = pattern.split('*');
+  let first = parts.next().unwrap_or("");
+  let mut rest = match path.strip_prefix(first) {
+    Some(rest) => rest,
+    None => return false,
+  };
+  let parts: Vec<&str> = parts.collect();
+  for (index, part) in parts.iter().enumerate() {
+    let is_last = index + 1 == parts.len();
+    if is_last && is_anchored {
+      return rest.ends_with(part);
+    }
+    match rest.find(part) {
+      Some(start) => rest = &rest[start + part.len()..],
+      None => return false,
+    }
+  }
+  !is_anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ROBOTS_TXT: &str = "# Example
+User-agent: *
+Disallow: /private/
+Allow: /private/public.html
+Disallow: /*.cgi$
+Crawl-delay: 2
+
+User-agent: BPOD
+User-agent: other-bot
+Disallow: /apod/archive
+Crawl-delay: 0.5
+
+User-agent: evil-bot
+Disallow: /
+";
+
+  #[test]
+  fn applies_longest_matching_rule() {
+    let robots = RobotsTxt::parse(ROBOTS_TXT);
+    assert!(robots.is_allowed("some-bot", "/apod/ap210101.html"));
+    assert!(!robots.is_allowed("some-bot", "/private/index.html"));
+    assert!(robots.is_allowed("some-bot", "/private/public.html"));
+    assert!(!robots.is_allowed("some-bot", "/cgi-bin/search.cgi"));
+    assert!(robots.is_allowed("some-bot", "/cgi-bin/search.cgi?q=m31"));
+    assert!(!robots.is_allowed("evil-bot", "/apod/ap210101.html"));
+    assert!(robots.is_allowed("evil-bot", "/robots.txt"));
+  }
+
+  #[test]
+  fn uses_group_of_agent() {
+    let robots = RobotsTxt::parse(ROBOTS_TXT);
+    assert!(!robots.is_allowed(ROBOTS_AGENT, "/apod/archivepix.html"));
+    assert!(robots.is_allowed(ROBOTS_AGENT, "/private/index.html"));
+    assert_eq!(robots.crawl_delay(ROBOTS_AGENT), Some(Duration::from_millis(500)));
+    assert_eq!(robots.crawl_delay("some-bot"), Some(Duration::from_secs(2)));
+  }
+
+  #[test]
+  fn handles_missing_and_empty_files() {
+    assert!(RobotsTxt::allow_all().is_allowed(ROBOTS_AGENT, "/apod/"));
+    assert!(!RobotsTxt::parse("User-agent: *\nDisallow: /").is_allowed(ROBOTS_AGENT, "/apod/"));
+    assert!(RobotsTxt::parse("User-agent: *\nDisallow:\n").is_allowed(ROBOTS_AGENT, "/apod/"));
+  }
+
+  #[test]
+  fn matches_wildcards() {
+    assert!(matches_pattern("/apod/*.jpg", "/apod/image/2101/m31.jpg"));
+    assert!(matches_pattern("/apod/*.jpg$", "/apod/image/2101/m31.jpg"));
+    assert!(!matches_pattern("/apod/*.jpg$", "/apod/image/2101/m31.jpg.html"));
+    assert!(matches_pattern("/apod/*", "/apod/"));
+    assert!(!matches_pattern("/image", "/apod/image"));
+  }
+}
@@ -0,0 +1,127 @@
+use crate::apod::Link;
+use chrono::NaiveDate;
+use regex::{Captures, Regex};
+
+/// Prefix of BPOD's own page for an APOD, followed by its date.
+pub const INTERNAL_LINK_PREFIX: &str = "/apod/";
+
+const APOD_PAGE_REGEX: &str =
+  r"https?://(?:apod\.nasa\.gov|antwrp\.gsfc\.nasa\.gov)/apod/ap(?P<yy>\d{2})(?P<mm>\d{2})(?P<dd>\d{2})\.html";
+
+/// Gets the date of the APOD a URL points to, e.g. `1999-01-05` for
+/// `https://apod.nasa.gov/apod/ap990105.html`. URLs with impossible dates
+/// like `ap991399.html` do not reference an APOD.
+pub fn get_referenced_date(url: &str) -> Option<String> {
+  let regex = Regex::new(&format!(r"^{}(?:#\S*)?$", APOD_PAGE_REGEX)).unwrap();
+  regex.captures(url).and_then(|captures| to_date(&captures))
+}
+
+/// Gets the dates of all other APODs the links point to, without duplicates.
+pub fn get_references(links: &[Link], date: &str) -> Vec<String> {
+  let mut references: Vec<String> = Vec::new();
+  for referenced_date in links.iter().filter_map(|link| get_referenced_date(&link.url)) {
+    if referenced_date != date && !references.contains(&referenced_date) {
+      references.push(referenced_date);
+    }
+  }
+  references
+}
+
+/// Rewrites links to APOD pages in normalized HTML into links to BPOD's own
+/// pages. Links with impossible dates are left as they are.
+pub fn rewrite_internal_links(html: &str) -> String {
+  let regex = Regex::new(&format!(r#"<a href="{}(?:#[^"]*)?""#, APOD_PAGE_REGEX)).unwrap();
+  regex
+    .replace_all(html, |captures: &Captures| match to_date(captures) {
+      Some(date) => format!(r#"<a href="{}{}""#, INTERNAL_LINK_PREFIX, date),
+      None => String::from(&captures[0]),
+    })
+    .to_string()
+}
+
+/// APOD started in 1995, so two-digit years from 95 on are in the 1900s.
+fn to_date(captures: &Captures) -> Option<String> {
+  let year: i32 = captures["yy"].parse().unwrap();
+  let century = match year >= 95 {
+    true => 1900,
+    false => 2000,
+  };
+  let month = captures["mm"].parse().unwrap();
+  let day = captures["dd"].parse().unwrap();
+  let date = NaiveDate::from_ymd_opt(century + year, month, day)?;
+  Some(date.format("%Y-%m-%d").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::apod::LinkSection;
+
+  fn link(url: &str) -> Link {
+    Link {
+      section: LinkSection::Explanation,
+      position: 0,
+      text: String::from("text"),
+      url: String::from(url),
+      domain: None,
+    }
+  }
+
+  #[test]
+  fn gets_referenced_date() {
+    assert_eq!(
+      get_referenced_date("https://apod.nasa.gov/apod/ap990105.html").as_deref(),
+      Some("1999-01-05")
+    );
+    assert_eq!(
+      get_referenced_date("http://antwrp.gsfc.nasa.gov/apod/ap210328.html#top").as_deref(),
+      Some("2021-03-28")
+    );
+    assert_eq!(
+      get_referenced_date("https://apod.nasa.gov/apod/archivepix.html"),
+      None
+    );
+    assert_eq!(
+      get_referenced_date("https://example.com/apod/ap210328.html"),
+      None
+    );
+  }
+
+  #[test]
+  fn skips_impossible_dates() {
+    assert_eq!(get_referenced_date("https://apod.nasa.gov/apod/ap991399.html"), None);
+    assert_eq!(get_referenced_date("https://apod.nasa.gov/apod/ap210230.html"), None);
+    let links = vec![
+      link("https://apod.nasa.gov/apod/ap991399.html"),
+      link("https://apod.nasa.gov/apod/ap210101.html"),
+    ];
+    assert_eq!(get_references(&links, "2021-03-29"), vec!["2021-01-01"]);
+    let html = r#"A <a href="https://apod.nasa.gov/apod/ap991399.html">broken</a> link"#;
+    assert_eq!(rewrite_internal_links(html), html);
+  }
+
+  #[test]
+  fn gets_distinct_references_to_other_apods() {
+    let links = vec![
+      link("https://apod.nasa.gov/apod/ap210101.html"),
+      link("https://en.wikipedia.org/wiki/Galaxy"),
+      link("https://apod.nasa.gov/apod/ap210101.html"),
+      link("https://apod.nasa.gov/apod/ap210329.html"),
+      link("https://apod.nasa.gov/apod/ap951231.html"),
+    ];
+    assert_eq!(
+      get_references(&links, "2021-03-29"),
+      vec!["2021-01-01", "1995-12-31"]
+    );
+  }
+
+  #[test]
+  fn rewrites_internal_links() {
+    assert_eq!(
+      rewrite_internal_links(
+        r#"An <a href="https://apod.nasa.gov/apod/ap210101.html">earlier</a> and an <a href="https://example.com/">external</a> link"#
+      ),
+      r#"An <a href="/apod/2021-01-01">earlier</a> and an <a href="https://example.com/">external</a> link"#
+    );
+  }
+}
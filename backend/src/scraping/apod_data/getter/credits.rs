@@ -0,0 +1,224 @@
+use super::super::normalization::{normalize_text, NormalizationContext};
+use super::utils::get_meta_block;
+use crate::apod::{Contributor, Credit, TextSection, COPYRIGHT_ROLE};
+use regex::Regex;
+
+/// Parses the meta block into credit entries. Labels like
+/// `Image Credit & Copyright:` give every named contributor both the credit
+/// role and the copyright role.
+pub fn get_credits(page: &str, context: &NormalizationContext) -> Vec<Credit> {
+  parse_credits(&normalize_text(get_meta_block(page), TextSection::Meta, context))
+}
+
+fn parse_credits(meta: &str) -> Vec<Credit> {
+  let bold_regex = Regex::new(r"<b>(?P<label>[\s\S]*?)</b>").unwrap();
+  let labels: Vec<(usize, usize, String)> = bold_regex
+    .captures_iter(meta)
+    .map(|captures| {
+      let whole = captures.get(0).unwrap();
+      (whole.start(), whole.end(), strip_tags(&captures["label"]))
+    })
+    .filter(|(_, _, label)| label.ends_with(':'))
+    .collect();
+
+  let mut credits: Vec<Credit> = Vec::new();
+  for (i, (_, content_start, label)) in labels.iter().enumerate() {
+    let content_end = labels.get(i + 1).map_or(meta.len(), |(start, _, _)| *start);
+    let roles = get_roles(label);
+    for contributor in parse_contributors(&meta[*content_start..content_end]) {
+      for role in roles.iter() {
+        credits.push(Credit {
+          role: role.clone(),
+          contributor: contributor.clone(),
+        });
+      }
+    }
+  }
+  credits
+}
+
+/// Splits a label like `Image Credit & Copyright:` into its roles.
+fn get_roles(label: &str) -> Vec<String> {
+  let label = label.trim_end_matches(':');
+  let mut roles: Vec<String> = Vec::new();
+  let mut has_copyright = false;
+  for part in Regex::new(r"\s*(?:&|\band\b)\s*").unwrap().split(label) {
+    let part = part.trim();
+    if part.eq_ignore_ascii_case(COPYRIGHT_ROLE) {
+      has_copyright = true;
+    } else if !part.is_empty() {
+      roles.push(String::from(part));
+    }
+  }
+  if has_copyright {
+    roles.push(String::from(COPYRIGHT_ROLE));
+  }
+  roles
+}
+
+/// Splits the content following a label into contributors. Separators inside
+/// links and parentheses (which usually hold affiliations) are ignored.
+fn parse_contributors(content: &str) -> Vec<Contributor> {
+  let mut entries: Vec<String> = Vec::new();
+  let mut entry = String::new();
+  let mut paren_depth = 0;
+  let mut in_link = false;
+  let mut rest = content;
+  while let Some(c) = rest.chars().next() {
+    if c == '<' {
+      let tag_end = rest.find('>').map_or(rest.len(), |i| i + 1);
+      let tag = &rest[..tag_end];
+      if tag.starts_with("<a ") {
+        in_link = true;
+      } else if tag == "</a>" {
+        in_link = false;
+      }
+      if tag == "<br>" && paren_depth == 0 {
+        entries.push(std::mem::take(&mut entry));
+      } else {
+        entry.push_str(tag);
+      }
+      rest = &rest[tag_end..];
+      continue;
+    }
+    match c {
+      '(' => paren_depth += 1,
+      ')' => paren_depth -= 1,
+      _ => (),
+    }
+    let at_top_level = paren_depth == 0 && !in_link;
+    let separator_length = match at_top_level {
+      true => get_separator_length(rest),
+      false => 0,
+    };
+    if separator_length > 0 {
+      entries.push(std::mem::take(&mut entry));
+      rest = &rest[separator_length..];
+    } else {
+      entry.push(c);
+      rest = &rest[c.len_utf8()..];
+    }
+  }
+  entries.push(entry);
+
+  entries
+    .iter()
+    .filter_map(|entry| parse_contributor(entry))
+    .collect()
+}
+
+fn get_separator_length(text: &str) -> usize {
+  for separator in [",", ";", " & ", " &amp; ", " and "].iter() {
+    if text.starts_with(separator) {
+      return separator.len();
+    }
+  }
+  0
+}
+
+fn parse_contributor(entry: &str) -> Option<Contributor> {
+  let name_part = entry.split('(').next().unwrap_or(entry);
+  let name = strip_tags(name_part);
+  let name = Regex::new(r"^(?:and|&)\s+")
+    .unwrap()
+    .replace(&name, "")
+    .trim_matches(|c: char| c.is_whitespace() || c == ':')
+    .to_string();
+  if name.is_empty() {
+    return None;
+  }
+  let url = Regex::new(r#"<a href="(?P<url>[^"]+)"[^>]*>"#)
+    .unwrap()
+    .captures(name_part)
+    .map(|captures| String::from(&captures["url"]));
+  Some(Contributor { name, url })
+}
+
+fn strip_tags(html: &str) -> String {
+  let text = Regex::new(r"<[^>]*>").unwrap().replace_all(html, "");
+  let text = text.replace("&amp;", "&").replace("&nbsp;", " ");
+  Regex::new(r"\s+")
+    .unwrap()
+    .replace_all(text.trim(), " ")
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn contributor(name: &str, url: Option<&str>) -> Contributor {
+    Contributor {
+      name: String::from(name),
+      url: url.map(String::from),
+    }
+  }
+
+  fn credit(role: &str, name: &str, url: Option<&str>) -> Credit {
+    Credit {
+      role: String::from(role),
+      contributor: contributor(name, url),
+    }
+  }
+
+  #[test]
+  fn parses_credit_and_copyright() {
+    assert_eq!(
+      parse_credits(
+        r#"<b>Image Credit &amp; <a href="https://apod.nasa.gov/apod/lib/about_apod.html#srapply">Copyright</a>:</b> <a href="https://www.astrobin.com/users/jdoe/">John Doe</a>"#
+      ),
+      vec![
+        credit("Image Credit", "John Doe", Some("https://www.astrobin.com/users/jdoe/")),
+        credit("Copyright", "John Doe", Some("https://www.astrobin.com/users/jdoe/")),
+      ]
+    );
+  }
+
+  #[test]
+  fn splits_multiple_contributors() {
+    assert_eq!(
+      parse_credits(
+        r#"<b>Image Credit:</b> <a href="https://www.nasa.gov/">NASA</a>, <a href="https://www.spacetelescope.org/">ESA</a>, and the Hubble Heritage Team (STScI/AURA)"#
+      ),
+      vec![
+        credit("Image Credit", "NASA", Some("https://www.nasa.gov/")),
+        credit("Image Credit", "ESA", Some("https://www.spacetelescope.org/")),
+        credit("Image Credit", "the Hubble Heritage Team", None),
+      ]
+    );
+  }
+
+  #[test]
+  fn parses_multiple_labels() {
+    assert_eq!(
+      parse_credits(
+        r#"<b>Video Credit:</b> <a href="https://www.eso.org/">ESO</a>; <b>Text:</b> Ogetay Kayali (<a href="https://www.mtu.edu/">Michigan Tech U.</a>)"#
+      ),
+      vec![
+        credit("Video Credit", "ESO", Some("https://www.eso.org/")),
+        credit("Text", "Ogetay Kayali", None),
+      ]
+    );
+  }
+
+  #[test]
+  fn keeps_separators_in_links() {
+    assert_eq!(
+      parse_credits(r#"<b>Copyright:</b> <a href="https://example.com/">Smith, Jones &amp; Co.</a>"#),
+      vec![credit(
+        "Copyright",
+        "Smith, Jones & Co.",
+        Some("https://example.com/")
+      )]
+    );
+  }
+
+  #[test]
+  fn ignores_bold_text_without_label() {
+    assert_eq!(
+      parse_credits(r#"<b>Image Credit:</b> Jane Roe <b>(important)</b>"#),
+      vec![credit("Image Credit", "Jane Roe", None)]
+    );
+  }
+}
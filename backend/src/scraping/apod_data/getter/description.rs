@@ -1,15 +1,18 @@
-use super::super::normalization::normalize_text;
+use super::super::cross_reference::rewrite_internal_links;
+use super::super::normalization::{normalize_text, NormalizationContext};
+use super::super::sanitization::sanitize_html;
 use super::super::translation::html_to_markdown;
-use regex::Regex;
+use super::utils::get_description_block;
+use crate::apod::TextSection;
 
-pub fn get_description(page: &str) -> String {
-  let regex =
-    Regex::new(r#"<.+?>\s*Explanation:\s*<.+?>\s*(?P<explanation>[\s\S]+?)\s*<p>"#).unwrap();
-  let raw_description = regex
-    .captures(page)
-    .unwrap()
-    .name("explanation")
-    .unwrap()
-    .as_str();
-  html_to_markdown(&normalize_text(raw_description))
+pub fn get_description(page: &str, context: &NormalizationContext) -> String {
+  html_to_markdown(&get_normalized_description(page, context))
+}
+
+pub fn get_description_html(page: &str, context: &NormalizationContext) -> String {
+  sanitize_html(&get_normalized_description(page, context))
+}
+
+fn get_normalized_description(page: &str, context: &NormalizationContext) -> String {
+  rewrite_internal_links(&normalize_text(get_description_block(page), TextSection::Explanation, context))
 }
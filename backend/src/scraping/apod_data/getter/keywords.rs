@@ -0,0 +1,156 @@
+use super::utils::get_attribute;
+use crate::apod::Tag;
+use regex::Regex;
+
+/// Keys of tags that are known under several names, mapped to the key and
+/// display name of the tag they are merged into.
+const TAG_ALIASES: [(&str, &str, &str); 12] = [
+  ("andromeda", "m31", "M31"),
+  ("andromeda galaxy", "m31", "M31"),
+  ("orion nebula", "m42", "M42"),
+  ("great orion nebula", "m42", "M42"),
+  ("crab nebula", "m1", "M1"),
+  ("pleiades", "m45", "M45"),
+  ("seven sisters", "m45", "M45"),
+  ("milky way galaxy", "milky way", "Milky Way"),
+  ("our galaxy", "milky way", "Milky Way"),
+  ("iss", "international space station", "International Space Station"),
+  ("hst", "hubble space telescope", "Hubble Space Telescope"),
+  ("hubble", "hubble space telescope", "Hubble Space Telescope"),
+];
+
+/// Gets the normalized tags from the page's keywords meta tag, without
+/// duplicates and in the order they are listed.
+pub fn get_keywords(page: &str) -> Vec<Tag> {
+  let content = match get_meta_content(page, "keywords") {
+    Some(content) => content,
+    None => return Vec::new(),
+  };
+  let mut tags: Vec<Tag> = Vec::new();
+  for keyword in content.split([',', ';']) {
+    if let Some(tag) = normalize_keyword(keyword) {
+      if !tags.iter().any(|known| known.key == tag.key) {
+        tags.push(tag);
+      }
+    }
+  }
+  tags
+}
+
+/// Gets the page's description meta tag.
+pub fn get_meta_description(page: &str) -> Option<String> {
+  get_meta_content(page, "description")
+}
+
+fn get_meta_content(page: &str, name: &str) -> Option<String> {
+  Regex::new(r"(?i)<meta\s[^>]*>")
+    .unwrap()
+    .find_iter(page)
+    .map(|tag| tag.as_str())
+    .find(|tag| {
+      get_attribute(tag, "name").is_some_and(|tag_name| tag_name.eq_ignore_ascii_case(name))
+    })
+    .and_then(|tag| get_attribute(tag, "content"))
+    .map(|content| decode_entities(&content))
+}
+
+/// Folds case and whitespace into a key, brings catalog designations like
+/// `Messier 31` or `NGC  7000` into a common form and merges known aliases.
+fn normalize_keyword(keyword: &str) -> Option<Tag> {
+  let name = Regex::new(r"\s+")
+    .unwrap()
+    .replace_all(keyword.trim_matches(|c: char| c.is_whitespace() || c == '.'), " ")
+    .to_string();
+  if name.is_empty() {
+    return None;
+  }
+
+  let catalog_regex = Regex::new(r"(?i)^(?P<catalog>m|messier|ngc|ic)\s*(?P<number>\d+)$").unwrap();
+  if let Some(captures) = catalog_regex.captures(&name) {
+    let catalog = match captures["catalog"].to_uppercase().as_str() {
+      "MESSIER" => String::from("M"),
+      other => String::from(other),
+    };
+    let separator = match catalog.as_str() {
+      "M" => "",
+      _ => " ",
+    };
+    let name = format!("{}{}{}", catalog, separator, &captures["number"]);
+    return Some(Tag {
+      key: name.to_lowercase(),
+      name,
+    });
+  }
+
+  let key = name.to_lowercase();
+  match TAG_ALIASES.iter().find(|(alias, _, _)| *alias == key) {
+    Some((_, key, name)) => Some(Tag {
+      key: String::from(*key),
+      name: String::from(*name),
+    }),
+    None => Some(Tag { key, name }),
+  }
+}
+
+fn decode_entities(text: &str) -> String {
+  text
+    .replace("&quot;", "\"")
+    .replace("&#39;", "'")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn keys(tags: &[Tag]) -> Vec<&str> {
+    tags.iter().map(|tag| tag.key.as_str()).collect()
+  }
+
+  #[test]
+  fn gets_keywords() {
+    let page = r#"<html><head><title>APOD</title><meta name="keywords" content="Arp 273, galaxies, interacting galaxies"></head>"#;
+    assert_eq!(
+      keys(&get_keywords(page)),
+      vec!["arp 273", "galaxies", "interacting galaxies"]
+    );
+  }
+
+  #[test]
+  fn gets_keywords_with_any_attribute_order_and_case() {
+    let page = r#"<META CONTENT="Moon" NAME="Keywords">"#;
+    assert_eq!(keys(&get_keywords(page)), vec!["moon"]);
+  }
+
+  #[test]
+  fn folds_case_and_whitespace() {
+    let page = r#"<meta name="keywords" content="Comet  NEOWISE, comet neowise ,Sun">"#;
+    let tags = get_keywords(page);
+    assert_eq!(keys(&tags), vec!["comet neowise", "sun"]);
+    assert_eq!(tags[0].name, "Comet NEOWISE");
+  }
+
+  #[test]
+  fn merges_catalog_designations_and_aliases() {
+    let page = r#"<meta name="keywords" content="M31, Messier 31, Andromeda Galaxy, NGC  7000, ngc7000, ISS">"#;
+    let tags = get_keywords(page);
+    assert_eq!(
+      keys(&tags),
+      vec!["m31", "ngc 7000", "international space station"]
+    );
+    assert_eq!(tags[1].name, "NGC 7000");
+  }
+
+  #[test]
+  fn gets_meta_description() {
+    let page = r#"<meta name="description" content="A different astronomy &amp; space science related image">"#;
+    assert_eq!(
+      get_meta_description(page).as_deref(),
+      Some("A different astronomy & space science related image")
+    );
+    assert_eq!(get_keywords(page), vec![]);
+  }
+}
@@ -0,0 +1,171 @@
+use super::super::normalization::{normalize_text, NormalizationContext};
+use super::utils::get_meta_block;
+use crate::apod::{Credit, License, LicenseClass, TextSection, COPYRIGHT_ROLE};
+use regex::Regex;
+
+/// Classifies the rights of the APOD's media from the meta block and the
+/// credits parsed from it. Linked or named Creative Commons licenses take
+/// precedence over copyright notices, and only media credited solely to NASA
+/// are considered public domain.
+pub fn get_license(page: &str, context: &NormalizationContext, credits: &[Credit]) -> License {
+  classify_license(&normalize_text(get_meta_block(page), TextSection::Meta, context), credits)
+}
+
+fn classify_license(meta: &str, credits: &[Credit]) -> License {
+  if let Some(license) = get_creative_commons_license(meta) {
+    return license;
+  }
+
+  let is_copyrighted = credits.iter().any(|credit| credit.role == COPYRIGHT_ROLE)
+    || Regex::new(r"(?i)(?:©|&copy;|\(c\))").unwrap().is_match(meta);
+  if is_copyrighted {
+    return License::new(LicenseClass::Copyrighted);
+  }
+
+  let media_credits: Vec<&Credit> = credits
+    .iter()
+    .filter(|credit| credit.role.contains("Credit"))
+    .collect();
+  let is_public_domain = Regex::new(r"(?i)public\s+domain").unwrap().is_match(meta)
+    || (!media_credits.is_empty()
+      && media_credits
+        .iter()
+        .all(|credit| is_nasa(&credit.contributor.name, &credit.contributor.url)));
+  if is_public_domain {
+    return License::new(LicenseClass::PublicDomain);
+  }
+
+  License::new(LicenseClass::Unknown)
+}
+
+fn get_creative_commons_license(meta: &str) -> Option<License> {
+  let link_regex = Regex::new(
+    r#"<a href="(?P<url>https?://creativecommons\.org/(?:licenses/(?P<kind>[a-z-]+)/(?P<version>[\d.]+)|publicdomain/(?P<pd>zero|mark)/(?P<pd_version>[\d.]+))[^"]*)""#,
+  )
+  .unwrap();
+  if let Some(captures) = link_regex.captures(meta) {
+    let url = Some(String::from(&captures["url"]));
+    return Some(match (captures.name("kind"), captures.name("pd")) {
+      (Some(kind), _) => License {
+        class: LicenseClass::CreativeCommons,
+        variant: Some(format!(
+          "CC {} {}",
+          kind.as_str().to_uppercase(),
+          &captures["version"]
+        )),
+        url,
+      },
+      (_, Some(pd)) if pd.as_str() == "zero" => License {
+        class: LicenseClass::CreativeCommons,
+        variant: Some(format!("CC0 {}", &captures["pd_version"])),
+        url,
+      },
+      _ => License {
+        class: LicenseClass::PublicDomain,
+        variant: None,
+        url,
+      },
+    });
+  }
+
+  let text_regex =
+    Regex::new(r"\bCC[ -](?P<kind>BY(?:-(?:SA|NC|ND))*)(?:[ -](?P<version>\d\.\d))?").unwrap();
+  text_regex.captures(meta).map(|captures| License {
+    class: LicenseClass::CreativeCommons,
+    variant: Some(match captures.name("version") {
+      Some(version) => format!("CC {} {}", &captures["kind"], version.as_str()),
+      None => format!("CC {}", &captures["kind"]),
+    }),
+    url: None,
+  })
+}
+
+fn is_nasa(name: &str, url: &Option<String>) -> bool {
+  let nasa_name_regex = Regex::new(r"\b(?:NASA|JPL|GSFC|JSC|MSFC)\b").unwrap();
+  let nasa_url_regex = Regex::new(r"^https?://(?:[^/]+\.)?nasa\.gov(?:/|$)").unwrap();
+  nasa_name_regex.is_match(name) || url.as_ref().is_some_and(|url| nasa_url_regex.is_match(url))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::apod::Contributor;
+
+  fn credit(role: &str, name: &str, url: Option<&str>) -> Credit {
+    Credit {
+      role: String::from(role),
+      contributor: Contributor {
+        name: String::from(name),
+        url: url.map(String::from),
+      },
+    }
+  }
+
+  #[test]
+  fn classifies_linked_creative_commons_license() {
+    let license = classify_license(
+      r#"<b>Image Credit:</b> Jane Roe; <b>License:</b> <a href="https://creativecommons.org/licenses/by-sa/4.0/">CC BY-SA</a>"#,
+      &[credit("Image Credit", "Jane Roe", None)],
+    );
+    assert_eq!(license.class, LicenseClass::CreativeCommons);
+    assert_eq!(license.variant.as_deref(), Some("CC BY-SA 4.0"));
+    assert_eq!(
+      license.url.as_deref(),
+      Some("https://creativecommons.org/licenses/by-sa/4.0/")
+    );
+  }
+
+  #[test]
+  fn classifies_named_creative_commons_license() {
+    let license = classify_license("<b>Image Credit:</b> Jane Roe (CC BY-NC 2.0)", &[]);
+    assert_eq!(license.class, LicenseClass::CreativeCommons);
+    assert_eq!(license.variant.as_deref(), Some("CC BY-NC 2.0"));
+  }
+
+  #[test]
+  fn classifies_cc0_license() {
+    let license = classify_license(
+      r#"<a href="https://creativecommons.org/publicdomain/zero/1.0/">CC0</a>"#,
+      &[],
+    );
+    assert_eq!(license.class, LicenseClass::CreativeCommons);
+    assert_eq!(license.variant.as_deref(), Some("CC0 1.0"));
+  }
+
+  #[test]
+  fn classifies_copyright() {
+    let license = classify_license(
+      "<b>Image Credit &amp; Copyright:</b> John Doe",
+      &[
+        credit("Image Credit", "John Doe", None),
+        credit(COPYRIGHT_ROLE, "John Doe", None),
+      ],
+    );
+    assert_eq!(license.class, LicenseClass::Copyrighted);
+  }
+
+  #[test]
+  fn classifies_nasa_images_as_public_domain() {
+    let license = classify_license(
+      "<b>Image Credit:</b> NASA, JPL-Caltech",
+      &[
+        credit("Image Credit", "NASA", Some("https://www.nasa.gov/")),
+        credit("Image Credit", "JPL-Caltech", Some("https://www.jpl.nasa.gov/")),
+        credit("Text", "Jane Roe", None),
+      ],
+    );
+    assert_eq!(license.class, LicenseClass::PublicDomain);
+  }
+
+  #[test]
+  fn classifies_mixed_credits_as_unknown() {
+    let license = classify_license(
+      "<b>Image Credit:</b> NASA, ESA",
+      &[
+        credit("Image Credit", "NASA", None),
+        credit("Image Credit", "ESA", None),
+      ],
+    );
+    assert_eq!(license.class, LicenseClass::Unknown);
+  }
+}
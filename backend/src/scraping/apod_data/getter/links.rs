@@ -0,0 +1,92 @@
+use super::super::normalization::{normalize_text, NormalizationContext};
+use super::utils::{get_description_block, get_meta_block};
+use crate::apod::{Link, LinkSection, TextSection};
+use regex::Regex;
+
+/// Gets all links of the explanation and the meta block in order of
+/// appearance. Positions are counted per section.
+pub fn get_links(page: &str, context: &NormalizationContext) -> Vec<Link> {
+  let mut links = parse_links(
+    &normalize_text(get_description_block(page), TextSection::Explanation, context),
+    LinkSection::Explanation,
+  );
+  links.append(&mut parse_links(
+    &normalize_text(get_meta_block(page), TextSection::Meta, context),
+    LinkSection::Meta,
+  ));
+  links
+}
+
+fn parse_links(html: &str, section: LinkSection) -> Vec<Link> {
+  Regex::new(r#"<a href="(?P<url>[^"]+)"[^>]*>(?P<text>[\s\S]*?)</a>"#)
+    .unwrap()
+    .captures_iter(html)
+    .enumerate()
+    .map(|(position, captures)| {
+      let url = String::from(&captures["url"]);
+      Link {
+        section: section.clone(),
+        position: position as u32,
+        text: get_anchor_text(&captures["text"]),
+        domain: get_domain(&url),
+        url,
+      }
+    })
+    .collect()
+}
+
+fn get_anchor_text(html: &str) -> String {
+  let text = Regex::new(r"<[^>]*>").unwrap().replace_all(html, "");
+  Regex::new(r"\s+")
+    .unwrap()
+    .replace_all(text.trim(), " ")
+    .replace("&amp;", "&")
+}
+
+/// Gets the lowercase host of the URL without a leading `www.`, so links to
+/// `https://WWW.nasa.gov` and `http://nasa.gov` count for the same source.
+fn get_domain(url: &str) -> Option<String> {
+  let host = Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*://(?:[^@/?#]*@)?(?P<host>[^/:?#]+)")
+    .unwrap()
+    .captures(url)?
+    .name("host")?
+    .as_str()
+    .to_lowercase();
+  Some(match host.strip_prefix("www.") {
+    Some(domain) => String::from(domain),
+    None => host,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_links_in_order() {
+    let links = parse_links(
+      r#"The <a href="https://hubblesite.org/">Hubble <b>Space</b> Telescope</a> imaged <a href="https://apod.nasa.gov/apod/ap210101.html">this galaxy</a>."#,
+      LinkSection::Explanation,
+    );
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0].position, 0);
+    assert_eq!(links[0].text, "Hubble Space Telescope");
+    assert_eq!(links[0].url, "https://hubblesite.org/");
+    assert_eq!(links[0].domain.as_deref(), Some("hubblesite.org"));
+    assert_eq!(links[1].position, 1);
+    assert_eq!(links[1].domain.as_deref(), Some("apod.nasa.gov"));
+  }
+
+  #[test]
+  fn gets_domain() {
+    assert_eq!(
+      get_domain("https://WWW.Nasa.gov:443/path?q=1").as_deref(),
+      Some("nasa.gov")
+    );
+    assert_eq!(
+      get_domain("ftp://user@ftp.example.org/file").as_deref(),
+      Some("ftp.example.org")
+    );
+    assert_eq!(get_domain("mailto:me@example.org"), None);
+  }
+}
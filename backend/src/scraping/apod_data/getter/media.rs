@@ -0,0 +1,255 @@
+use super::super::normalization::{normalize_url, NormalizationContext};
+use super::utils::{get_attribute, get_media_block};
+use crate::apod::{ImageFile, Media, MediaRole, Video};
+use crate::scraping::video::parse_video_url;
+use regex::Regex;
+
+/// Returns all media shown on the page in order of appearance. The first one
+/// is the primary media; images revealed on mouse-over directly follow the
+/// image they replace.
+pub fn get_media(page: &str, context: &NormalizationContext) -> Vec<Media> {
+  let block = get_media_block(page);
+  let element_regex =
+    Regex::new(r"(?i)</?(?:a|img|iframe|object|embed|video|source)\b[^>]*>").unwrap();
+
+  let mut media: Vec<Media> = Vec::new();
+  let mut link_url: Option<String> = None;
+  // Index of the video or object element the parser is currently inside of.
+  let mut container_index: Option<usize> = None;
+  let mut poster_url: Option<String> = None;
+
+  for element in element_regex.find_iter(block) {
+    let tag = element.as_str();
+    match get_tag_name(tag).as_str() {
+      "a" => link_url = get_attribute(tag, "href"),
+      "/a" => link_url = None,
+      "video" => {
+        poster_url = get_attribute(tag, "poster").map(|url| resolve_url(&url, context));
+        let url = get_attribute(tag, "src").unwrap_or_default();
+        container_index = Some(media.len());
+        let item = media_from_url(&media, &url, &poster_url, context);
+        media.push(item);
+      }
+      "source" => {
+        // Only the first source is used, all further ones are the same video
+        // in other formats.
+        if let (Some(index), Some(url)) = (container_index, get_attribute(tag, "src")) {
+          if media[index].img.url.is_empty() {
+            media[index] = media_from_url(&media[..index], &url, &poster_url, context);
+          }
+        }
+      }
+      "img" => {
+        let url = match get_attribute(tag, "src") {
+          Some(url) => resolve_url(&url, context),
+          None => continue,
+        };
+        let role = match container_index {
+          Some(_) => MediaRole::Alternate,
+          None => get_role(&media, &url),
+        };
+        let mut img = Media::new(role, url);
+        img.hires_img = link_url
+          .as_ref()
+          .filter(|url| !url.ends_with(".html") && !url.ends_with(".htm"))
+          .map(|url| ImageFile::new(resolve_url(url, context)));
+        let mouse_over_url = get_attribute(tag, "onmouseover").and_then(|js| get_swapped_src(&js));
+        media.push(img);
+        if let Some(url) = mouse_over_url {
+          let url = resolve_url(&url, context);
+          let role = match is_annotated(&url) {
+            true => MediaRole::Annotated,
+            false => MediaRole::Comparison,
+          };
+          media.push(Media::new(role, url));
+        }
+      }
+      "iframe" | "embed" => {
+        if let Some(url) = get_attribute(tag, "src") {
+          let item = media_from_url(&media, &url, &None, context);
+          media.push(item);
+        }
+      }
+      "object" => {
+        if let Some(url) = get_attribute(tag, "data") {
+          container_index = Some(media.len());
+          let item = media_from_url(&media, &url, &None, context);
+          media.push(item);
+        }
+      }
+      "/video" | "/object" => container_index = None,
+      _ => (),
+    }
+  }
+
+  media.retain(|item| !item.img.url.is_empty());
+  media
+}
+
+/// Creates a media item for a URL that may point to a video. Empty URLs give a
+/// placeholder that is filled once the video's source element is found.
+fn media_from_url(
+  previous_media: &[Media],
+  url: &str,
+  poster_url: &Option<String>,
+  context: &NormalizationContext,
+) -> Media {
+  if url.is_empty() {
+    return Media::new(get_role(previous_media, ""), String::new());
+  }
+  let url = resolve_url(url, context);
+  let video = parse_video_url(&url).map(|video| Video {
+    poster_url: poster_url.clone(),
+    ..video
+  });
+  Media {
+    video,
+    ..Media::new(get_role(previous_media, &url), url)
+  }
+}
+
+/// Resolves a media URL against the page's base URL. Unparseable URLs are kept
+/// as they are and reported as warning.
+fn resolve_url(url: &str, context: &NormalizationContext) -> String {
+  normalize_url(url, &context.base_url).unwrap_or_else(|warning| {
+    context.warn(warning);
+    String::from(url.trim())
+  })
+}
+
+/// The first media is the primary one. Later media are alternates if they are
+/// of another kind than the primary one (e.g. an animation next to a still
+/// image) and comparisons otherwise, unless their file name marks them as
+/// annotated.
+fn get_role(previous_media: &[Media], url: &str) -> MediaRole {
+  let primary = match previous_media.first() {
+    Some(primary) => primary,
+    None => return MediaRole::Primary,
+  };
+  if is_annotated(url) {
+    MediaRole::Annotated
+  } else if is_moving(url) != is_moving(&primary.img.url) {
+    MediaRole::Alternate
+  } else {
+    MediaRole::Comparison
+  }
+}
+
+fn is_moving(url: &str) -> bool {
+  parse_video_url(url).is_some() || url.to_lowercase().ends_with(".gif")
+}
+
+fn is_annotated(url: &str) -> bool {
+  Regex::new(r"(?i)(?:annot|label|marked)")
+    .unwrap()
+    .is_match(url.rsplit('/').next().unwrap_or(url))
+}
+
+fn get_tag_name(tag: &str) -> String {
+  Regex::new(r"^<(/?[a-zA-Z]+)")
+    .unwrap()
+    .captures(tag)
+    .map(|captures| captures[1].to_lowercase())
+    .unwrap_or_default()
+}
+
+/// Gets the image URL from mouse-over handlers like `this.src='image.jpg'`.
+fn get_swapped_src(script: &str) -> Option<String> {
+  Regex::new(r#"src\s*=\s*['"]?(?P<url>[^'";\s]+)"#)
+    .unwrap()
+    .captures(script)
+    .map(|captures| String::from(&captures["url"]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::apod::VideoProvider;
+
+  fn get_media_of(page: &str) -> Vec<Media> {
+    get_media(page, &NormalizationContext::default())
+  }
+
+  fn page(media_block: &str) -> String {
+    format!(
+      "<center>\n<h1> Astronomy Picture of the Day </h1>\n<p>\n<a href=\"archivepix.html\">Discover the cosmos!</a>\n<p>\n2021 March 29\n<br>\n{}\n</center>\n<center>\n<b> A Title </b> <br>\n<b>Image Credit: </b>Someone\n</center>",
+      media_block
+    )
+  }
+
+  #[test]
+  fn gets_image_with_enclosing_link() {
+    let media = get_media_of(&page("<a href=\"image/2103/Arp273_2048.jpg\">\n<IMG SRC=\"image/2103/Arp273_1024.jpg\"\nalt=\"See Explanation.\" style=\"max-width:100%\"></a>"));
+    assert_eq!(media.len(), 1);
+    assert_eq!(media[0].role, MediaRole::Primary);
+    assert_eq!(media[0].img.url, "https://apod.nasa.gov/apod/image/2103/Arp273_1024.jpg");
+    assert_eq!(
+      media[0].hires_img.as_ref().unwrap().url,
+      "https://apod.nasa.gov/apod/image/2103/Arp273_2048.jpg"
+    );
+  }
+
+  #[test]
+  fn ignores_link_to_html_page() {
+    let media = get_media_of(&page("<a href=\"ap210328.html\"><img src=\"image/2103/Arp273_1024.jpg\"></a>"));
+    assert!(media[0].hires_img.is_none());
+  }
+
+  #[test]
+  fn gets_image_pairs() {
+    let media = get_media_of(&page("<a href=\"image/2103/Before.jpg\"><img src=\"image/2103/Before_s.jpg\"></a><a href=\"image/2103/After.jpg\"><img src=\"image/2103/After_s.jpg\"></a>"));
+    assert_eq!(media.len(), 2);
+    assert_eq!(media[0].role, MediaRole::Primary);
+    assert_eq!(media[1].role, MediaRole::Comparison);
+    assert_eq!(
+      media[1].hires_img.as_ref().unwrap().url,
+      "https://apod.nasa.gov/apod/image/2103/After.jpg"
+    );
+  }
+
+  #[test]
+  fn gets_mouse_over_images() {
+    let media = get_media_of(&page("<img src=\"image/2103/M31.jpg\" onMouseOver=\"this.src='image/2103/M31_labeled.jpg';\" onMouseOut=\"this.src='image/2103/M31.jpg';\">"));
+    assert_eq!(media.len(), 2);
+    assert_eq!(media[1].role, MediaRole::Annotated);
+    assert_eq!(media[1].img.url, "https://apod.nasa.gov/apod/image/2103/M31_labeled.jpg");
+  }
+
+  #[test]
+  fn gets_image_map() {
+    let media = get_media_of(&page("<img src=\"image/2103/Map.jpg\" usemap=\"#Map\">\n<map name=\"Map\"><area shape=\"rect\" coords=\"0,0,10,10\" href=\"ap200101.html\"></map>"));
+    assert_eq!(media.len(), 1);
+    assert_eq!(media[0].img.url, "https://apod.nasa.gov/apod/image/2103/Map.jpg");
+  }
+
+  #[test]
+  fn gets_video_with_still_fallback() {
+    let media = get_media_of(&page("<video width=\"960\" controls autoplay loop muted poster=\"image/2103/Eclipse_poster.jpg\">\n<source src=\"image/2103/Eclipse.mp4\" type=\"video/mp4\">\n<source src=\"image/2103/Eclipse.webm\" type=\"video/webm\">\n<img src=\"image/2103/Eclipse.jpg\"></video>"));
+    assert_eq!(media.len(), 2);
+    assert_eq!(media[0].role, MediaRole::Primary);
+    let video = media[0].video.as_ref().unwrap();
+    assert_eq!(video.provider, VideoProvider::SelfHosted);
+    assert_eq!(video.watch_url, "https://apod.nasa.gov/apod/image/2103/Eclipse.mp4");
+    assert_eq!(
+      video.poster_url.as_deref(),
+      Some("https://apod.nasa.gov/apod/image/2103/Eclipse_poster.jpg")
+    );
+    assert_eq!(media[1].role, MediaRole::Alternate);
+    assert_eq!(media[1].img.url, "https://apod.nasa.gov/apod/image/2103/Eclipse.jpg");
+  }
+
+  #[test]
+  fn gets_embedded_youtube_video() {
+    let media = get_media_of(&page("<iframe width=\"960\" height=\"540\"\n src=\"https://www.youtube.com/embed/dQw4w9WgXcQ?rel=0\"\n frameborder=\"0\" allowfullscreen></iframe>"));
+    assert_eq!(media.len(), 1);
+    assert_eq!(media[0].video.as_ref().unwrap().provider, VideoProvider::YouTube);
+  }
+
+  #[test]
+  fn gets_image_plus_animation() {
+    let media = get_media_of(&page("<a href=\"image/2103/Comet.jpg\"><img src=\"image/2103/Comet_s.jpg\"></a>\n<br>\n<a href=\"image/2103/Comet_anim.gif\"><img src=\"image/2103/Comet_anim_s.gif\"></a>"));
+    assert_eq!(media.len(), 2);
+    assert_eq!(media[1].role, MediaRole::Alternate);
+    assert_eq!(media[1].img.url, "https://apod.nasa.gov/apod/image/2103/Comet_anim_s.gif");
+  }
+}
@@ -1,16 +1,18 @@
-use super::super::normalization::normalize_text;
+use super::super::cross_reference::rewrite_internal_links;
+use super::super::normalization::{normalize_text, NormalizationContext};
+use super::super::sanitization::sanitize_html;
 use super::super::translation::html_to_markdown;
-use super::utils::get_title_meta_block;
-use regex::Regex;
+use super::utils::get_meta_block;
+use crate::apod::TextSection;
 
-pub fn get_meta(page: &str) -> String {
-  let title_meta_block = get_title_meta_block(page);
-  let meta_block = Regex::new(r"<[^>]+?>[\s\S]+?</[^>]+?>\s*(?:<br>)?\s*(?P<amb>[\s\S]+)")
-    .expect("Regex for additional meta block invalid")
-    .captures(title_meta_block)
-    .expect("Could not find additional meta block content")
-    .name("amb")
-    .expect("Could not get meta block content")
-    .as_str();
-  html_to_markdown(&normalize_text(meta_block).replace("*", ""))
+pub fn get_meta(page: &str, context: &NormalizationContext) -> String {
+  html_to_markdown(&get_normalized_meta(page, context).replace("*", ""))
+}
+
+pub fn get_meta_html(page: &str, context: &NormalizationContext) -> String {
+  sanitize_html(&get_normalized_meta(page, context))
+}
+
+fn get_normalized_meta(page: &str, context: &NormalizationContext) -> String {
+  rewrite_internal_links(&normalize_text(get_meta_block(page), TextSection::Meta, context))
 }
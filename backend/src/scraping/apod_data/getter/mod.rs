@@ -1,10 +1,19 @@
+mod credits;
 mod description;
-mod img_url;
+mod keywords;
+mod license;
+mod links;
+mod media;
 mod meta;
 mod title;
 mod utils;
 
-pub use description::get_description;
-pub use img_url::get_img_url;
-pub use meta::get_meta;
+pub use credits::get_credits;
+pub use description::{get_description, get_description_html};
+pub use keywords::{get_keywords, get_meta_description};
+pub use license::get_license;
+pub use links::get_links;
+pub use media::get_media;
+pub use meta::{get_meta, get_meta_html};
 pub use title::get_title;
+pub use utils::get_attribute;
@@ -1,16 +1,50 @@
-use super::super::normalization::normalize_text;
+use super::super::normalization::{normalize_text, NormalizationContext};
+use super::super::sanitization::sanitize_html;
 use super::super::translation::html_to_markdown;
 use super::utils::get_title_meta_block;
+use crate::apod::TextSection;
 use regex::Regex;
 
-pub fn get_title(page: &str) -> String {
+/// Gets the title as plain text and as sanitized HTML, without the bold tag
+/// wrapping it. The title is normalized once for both, so that its repairs are
+/// recorded once.
+pub fn get_title(page: &str, context: &NormalizationContext) -> (String, String) {
+  let title = normalize_text(get_raw_title(page), TextSection::Title, context);
+  (html_to_markdown(&title).replace("*", ""), sanitize_html(&title))
+}
+
+/// Returns the title without its enclosing tag.
+fn get_raw_title(page: &str) -> &str {
   let meta_block = get_title_meta_block(page);
   let regex =
     Regex::new(r"<[^>]+?>\s*(\S[\s\S]+?\S)\s*</[^>]+?>").expect("Regex for title invalid");
-  let raw_title = regex
-    .find_iter(meta_block)
-    .nth(0)
+  regex
+    .captures(meta_block)
     .expect("Could not find title")
-    .as_str();
-  html_to_markdown(&normalize_text(raw_title)).replace("*", "")
+    .get(1)
+    .unwrap()
+    .as_str()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn records_title_repairs_once() {
+    let page = "<center><h1>Astronomy Picture of the Day</h1></center>
+      <center>
+      <b> M31: The Andromeda<BR>Galaxy </b> <br>
+      <b>Image Credit:</b> John Doe
+      </center>";
+    let context = NormalizationContext::default();
+    let (_, html) = get_title(page, &context);
+    assert_eq!(html, "M31: The Andromeda<br>Galaxy");
+    let rules: Vec<String> = context
+      .take_repairs()
+      .into_iter()
+      .map(|repair| repair.rule)
+      .collect();
+    assert_eq!(rules, vec!["lowercase-name"]);
+  }
 }
@@ -15,3 +15,59 @@ pub fn get_title_meta_block(page: &str) -> &str {
     .expect("Could not get meta block content")
     .as_str()
 }
+
+/// Returns the HTML of the explanation without its label.
+pub fn get_description_block(page: &str) -> &str {
+  Regex::new(r#"<.+?>\s*Explanation:\s*<.+?>\s*(?P<explanation>[\s\S]+?)\s*<p>"#)
+    .unwrap()
+    .captures(page)
+    .unwrap()
+    .name("explanation")
+    .unwrap()
+    .as_str()
+}
+
+/// Returns the HTML following the title in the title and meta block, i.e. the
+/// credits, copyright and license information.
+pub fn get_meta_block(page: &str) -> &str {
+  let title_meta_block = get_title_meta_block(page);
+  Regex::new(r"<[^>]+?>[\s\S]+?</[^>]+?>\s*(?:<br>)?\s*(?P<amb>[\s\S]+)")
+    .expect("Regex for additional meta block invalid")
+    .captures(title_meta_block)
+    .expect("Could not find additional meta block content")
+    .name("amb")
+    .expect("Could not get meta block content")
+    .as_str()
+}
+
+/// Returns the part of the page in front of the title and meta block, which
+/// holds the APOD's images and videos.
+pub fn get_media_block(page: &str) -> &str {
+  match Regex::new(r"<center>[\s\S]+?</center>")
+    .expect("Regex for media block invalid")
+    .find_iter(page)
+    .nth(1)
+  {
+    Some(title_meta_block) => &page[..title_meta_block.start()],
+    None => page,
+  }
+}
+
+/// Gets the value of an attribute of an HTML tag, no matter how it is quoted.
+pub fn get_attribute(tag: &str, name: &str) -> Option<String> {
+  let regex = format!(
+    r#"(?i)\s{}\s*=\s*(?:"(?P<dq>[^"]*)"|'(?P<sq>[^']*)'|(?P<uq>[^\s>]+))"#,
+    name
+  );
+  let captures = Regex::new(&regex).unwrap().captures(tag)?;
+  let value = captures
+    .name("dq")
+    .or_else(|| captures.name("sq"))
+    .or_else(|| captures.name("uq"))?
+    .as_str()
+    .trim();
+  match value.is_empty() {
+    true => None,
+    false => Some(String::from(value)),
+  }
+}
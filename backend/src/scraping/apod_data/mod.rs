@@ -1,34 +1,86 @@
+mod cross_reference;
 mod getter;
 mod normalization;
+mod sanitization;
 mod translation;
 
-use super::error::ScrapeResult;
-use crate::apod::APOD;
+use super::error::{ScrapeError, ScrapeResult};
+use super::image_file::get_image_file;
+use crate::apod::{HtmlText, ImageFile, APOD};
 use crate::APODRequestClient;
-use getter::{get_description, get_img_url, get_meta, get_title};
+use cross_reference::get_references;
+use getter::{
+  get_credits, get_description, get_description_html, get_keywords, get_license, get_links,
+  get_media, get_meta, get_meta_description, get_meta_html, get_title,
+};
+use normalization::{get_base_url, NormalizationContext};
+pub use normalization::lint_html;
+use url::Url;
 
 pub async fn get_apod_data(date: &str, client: &APODRequestClient) -> ScrapeResult<Option<APOD>> {
   let year = &date[2..4];
   let month = &date[5..7];
   let day = &date[8..10];
   let url = format!("https://apod.nasa.gov/apod/ap{}{}{}.html", year, month, day);
-  let page_response = client.get(&url).await?;
-  if page_response.status().as_u16() == 404 {
-    return Ok(None);
-  }
-  let page = page_response.text().await.expect("Could not get text");
+  let page_response = match client.get(&url).await {
+    Ok(response) => response,
+    Err(ScrapeError::Status(404)) => return Ok(None),
+    Err(err) => return Err(err),
+  };
+  let page = page_response
+    .text()
+    .await
+    .map_err(|_| ScrapeError::Network)?;
+  let page_url = Url::parse(&url).map_err(|_| ScrapeError::Parsing)?;
+  let context = NormalizationContext::new(get_base_url(&page, &page_url));
+
+  let description = get_description(&page, &context);
+  let mut media = get_media(&page, &context);
+  let (title, title_html) = get_title(&page, &context);
+  let meta = get_meta(&page, &context);
+  let html = HtmlText {
+    title: title_html,
+    description: get_description_html(&page, &context),
+    meta: get_meta_html(&page, &context),
+  };
+  let credits = get_credits(&page, &context);
+  let license = get_license(&page, &context, &credits);
+  let summary = get_meta_description(&page);
+  let tags = get_keywords(&page);
+  let links = get_links(&page, &context);
+  let references = get_references(&links, date);
 
-  let description = get_description(&page);
-  let img_url = get_img_url(&page);
-  let title = get_title(&page);
-  let meta = get_meta(&page);
+  if media.is_empty() {
+    return Err(ScrapeError::Parsing);
+  }
+  for item in media.iter_mut() {
+    item.img = probe_image_file(&item.img.url, client).await;
+    if let Some(hires_img) = item.hires_img.as_ref() {
+      item.hires_img = Some(probe_image_file(&hires_img.url, client).await);
+    }
+  }
 
   Ok(Some(APOD {
     id: None,
     date: String::from(date),
-    img_url,
+    media,
     title,
     description,
     meta,
+    html,
+    credits,
+    license,
+    summary,
+    tags,
+    links,
+    references,
+    warnings: context.take_warnings(),
+    repairs: context.take_repairs(),
   }))
 }
+
+async fn probe_image_file(url: &str, client: &APODRequestClient) -> ImageFile {
+  get_image_file(url, client)
+    .await
+    .unwrap_or_else(|_| ImageFile::new(String::from(url)))
+}
@@ -0,0 +1,214 @@
+use super::NormalizationContext;
+use crate::apod::TextSection;
+use regex::Regex;
+
+/// Elements whose tags have to come in pairs for the Markdown translation.
+const BALANCED_ELEMENTS: [&str; 4] = ["a", "b", "i", "center"];
+
+/// Turns normalized HTML into a well-formed tree of the elements in
+/// `BALANCED_ELEMENTS`: closing tags without opening tag are dropped, elements
+/// closed before elements opened inside them are split up
+/// (`<b><i>x</b>y</i>` becomes `<b><i>x</i></b><i>y</i>`), a link inside a
+/// link closes the outer one, empty elements are removed and elements still
+/// open at the end are closed there.
+pub fn balance_tags(html: &str, section: TextSection, context: &NormalizationContext) -> String {
+  let tag_regex = Regex::new(r"<(?P<closing>/)?(?P<name>[a-z]+)[^>]*>").unwrap();
+  let mut balancer = Balancer {
+    balanced: String::new(),
+    open_elements: Vec::new(),
+    section,
+    context,
+  };
+  let mut last_end = 0;
+
+  for captures in tag_regex.captures_iter(html) {
+    let tag = captures.get(0).unwrap();
+    let name = &captures["name"];
+    balancer.balanced.push_str(&html[last_end..tag.start()]);
+    last_end = tag.end();
+    if !BALANCED_ELEMENTS.contains(&name) {
+      balancer.balanced.push_str(tag.as_str());
+      continue;
+    }
+
+    let open_index = balancer
+      .open_elements
+      .iter()
+      .rposition(|open| open.name == name);
+    if captures.name("closing").is_none() {
+      if let Some(index) = open_index.filter(|_| name == "a") {
+        balancer.close_early(index, "nested-links", tag.start(), tag.as_str());
+      }
+      balancer.open(OpenElement {
+        name: String::from(name),
+        tag: String::from(tag.as_str()),
+        offset: tag.start(),
+        start: 0,
+        is_reopened: false,
+      });
+      continue;
+    }
+
+    match open_index {
+      Some(index) if index + 1 == balancer.open_elements.len() => {
+        let open = balancer.open_elements.pop().unwrap();
+        balancer.close(&open);
+      }
+      Some(index) => balancer.close_early(index, "misnested-tags", tag.start(), tag.as_str()),
+      None => context.repair("stray-closing-tag", section, tag.start(), tag.as_str(), ""),
+    }
+  }
+  balancer.balanced.push_str(&html[last_end..]);
+
+  let mut closing_tags = String::new();
+  while let Some(open) = balancer.open_elements.pop() {
+    closing_tags.push_str(&balancer.close(&open));
+  }
+  if !closing_tags.is_empty() {
+    context.repair("unclosed-tag", section, html.len(), "", &closing_tags);
+  }
+  balancer.balanced
+}
+
+/// An element opened but not closed yet.
+struct OpenElement {
+  name: String,
+  tag: String,
+  /// Where the opening tag starts in the section.
+  offset: usize,
+  /// Where the opening tag starts in the balanced HTML.
+  start: usize,
+  /// Whether the element was opened again after being closed early.
+  is_reopened: bool,
+}
+
+struct Balancer<'a> {
+  balanced: String,
+  open_elements: Vec<OpenElement>,
+  section: TextSection,
+  context: &'a NormalizationContext,
+}
+
+impl Balancer<'_> {
+  fn open(&mut self, mut element: OpenElement) {
+    element.start = self.balanced.len();
+    self.balanced.push_str(&element.tag);
+    self.open_elements.push(element);
+  }
+
+  /// Closes an element that was already removed from the open elements and
+  /// returns the closing tag added. Elements without content are removed
+  /// instead.
+  fn close(&mut self, element: &OpenElement) -> String {
+    if self.balanced.len() > element.start + element.tag.len() {
+      let closing_tag = format!("</{}>", element.name);
+      self.balanced.push_str(&closing_tag);
+      return closing_tag;
+    }
+    self.balanced.truncate(element.start);
+    if !element.is_reopened {
+      self.context.repair(
+        "empty-element",
+        self.section,
+        element.offset,
+        &element.tag,
+        "",
+      );
+    }
+    String::new()
+  }
+
+  /// Closes the open element at `index` together with all elements opened
+  /// inside it, which are opened again right after.
+  fn close_early(&mut self, index: usize, rule: &str, offset: usize, tag: &str) {
+    let mut closed = self.open_elements.split_off(index);
+    let mut after = String::new();
+    for element in closed.iter().rev() {
+      after.push_str(&self.close(element));
+    }
+    for mut element in closed.drain(1..) {
+      after.push_str(&element.tag);
+      element.is_reopened = true;
+      self.open(element);
+    }
+    self.context.repair(rule, self.section, offset, tag, &after);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn balance_tags(html: &str) -> String {
+    super::balance_tags(html, TextSection::Explanation, &NormalizationContext::default())
+  }
+
+  fn get_rules(html: &str) -> Vec<String> {
+    let context = NormalizationContext::default();
+    super::balance_tags(html, TextSection::Explanation, &context);
+    context
+      .take_repairs()
+      .into_iter()
+      .map(|repair| repair.rule)
+      .collect()
+  }
+
+  #[test]
+  fn keeps_balanced_tags() {
+    let html = r#"<b>Explanation:</b> Is <a href="https://apod.nasa.gov/apod/ap960101.html">this</a> a <i>galaxy</i>?"#;
+    assert_eq!(balance_tags(html), html);
+    assert!(get_rules(html).is_empty());
+  }
+
+  #[test]
+  fn closes_unclosed_tags() {
+    assert_eq!(
+      balance_tags("<b>Picture Credit:</b> <i>Hubble Heritage Team"),
+      "<b>Picture Credit:</b> <i>Hubble Heritage Team</i>"
+    );
+    assert_eq!(get_rules("<b>Credit: NASA"), vec!["unclosed-tag"]);
+  }
+
+  #[test]
+  fn reorders_misnested_tags() {
+    assert_eq!(
+      balance_tags("<b><i>Sojourner</b> Rover</i>"),
+      "<b><i>Sojourner</i></b><i> Rover</i>"
+    );
+    assert_eq!(
+      balance_tags(r#"<b><a href="https://www.nasa.gov/">Credit</b></a>: NASA"#),
+      r#"<b><a href="https://www.nasa.gov/">Credit</a></b>: NASA"#
+    );
+    assert_eq!(
+      get_rules(r#"<b><a href="https://www.nasa.gov/">Credit</b></a>: NASA"#),
+      vec!["misnested-tags"]
+    );
+  }
+
+  #[test]
+  fn drops_stray_closing_tags() {
+    assert_eq!(
+      balance_tags("Explanation:</b> The Moon</i> rises."),
+      "Explanation: The Moon rises."
+    );
+    assert_eq!(
+      get_rules("Explanation:</b> The Moon rises."),
+      vec!["stray-closing-tag"]
+    );
+  }
+
+  #[test]
+  fn closes_links_before_nested_links() {
+    assert_eq!(
+      balance_tags(r#"<a href="https://a.example/">A <a href="https://b.example/">B</a>"#),
+      r#"<a href="https://a.example/">A </a><a href="https://b.example/">B</a>"#
+    );
+  }
+
+  #[test]
+  fn removes_empty_elements() {
+    assert_eq!(balance_tags("The <b></b>Sun<i>"), "The Sun");
+    assert_eq!(get_rules("The <b></b>Sun"), vec!["empty-element"]);
+  }
+}
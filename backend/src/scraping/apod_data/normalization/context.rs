@@ -0,0 +1,73 @@
+use crate::apod::{Repair, TextSection};
+use std::cell::RefCell;
+use url::Url;
+
+/// Maximum length of the before and after snippets of repairs, in characters.
+const MAX_SNIPPET_LENGTH: usize = 200;
+
+/// The base URL every APOD page is located at.
+pub const APOD_BASE_URL: &str = "https://apod.nasa.gov/apod/";
+
+/// State shared by all normalizations of one page: the URL relative links are
+/// resolved against and the warnings and repairs collected along the way.
+pub struct NormalizationContext {
+  pub base_url: Url,
+  warnings: RefCell<Vec<String>>,
+  repairs: RefCell<Vec<Repair>>,
+}
+
+impl NormalizationContext {
+  pub fn new(base_url: Url) -> Self {
+    Self {
+      base_url,
+      warnings: RefCell::new(Vec::new()),
+      repairs: RefCell::new(Vec::new()),
+    }
+  }
+
+  /// Records a warning. Blocks are normalized by several getters, so warnings
+  /// that were already recorded are ignored.
+  pub fn warn(&self, warning: String) {
+    let mut warnings = self.warnings.borrow_mut();
+    if !warnings.contains(&warning) {
+      warnings.push(warning);
+    }
+  }
+
+  pub fn take_warnings(&self) -> Vec<String> {
+    self.warnings.replace(Vec::new())
+  }
+
+  /// Records that a rule repaired `before` into `after` at `offset` of a
+  /// section, ignoring repairs that were already recorded.
+  pub fn repair(&self, rule: &str, section: TextSection, offset: usize, before: &str, after: &str) {
+    let repair = Repair {
+      rule: String::from(rule),
+      section,
+      offset: offset as u32,
+      before: shorten(before),
+      after: shorten(after),
+    };
+    let mut repairs = self.repairs.borrow_mut();
+    if !repairs.contains(&repair) {
+      repairs.push(repair);
+    }
+  }
+
+  pub fn take_repairs(&self) -> Vec<Repair> {
+    self.repairs.replace(Vec::new())
+  }
+}
+
+fn shorten(snippet: &str) -> String {
+  match snippet.char_indices().nth(MAX_SNIPPET_LENGTH) {
+    Some((end, _)) => format!("{}…", &snippet[..end]),
+    None => String::from(snippet),
+  }
+}
+
+impl Default for NormalizationContext {
+  fn default() -> Self {
+    Self::new(Url::parse(APOD_BASE_URL).unwrap())
+  }
+}
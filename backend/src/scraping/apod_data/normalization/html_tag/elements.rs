@@ -0,0 +1,128 @@
+/// An element the normalization knows, with the attributes kept on it.
+pub struct Element {
+  pub name: &'static str,
+  pub attributes: &'static [&'static str],
+  /// The element this one is written as in normalized HTML, e.g. `b` for
+  /// `strong`.
+  pub canonical_name: Option<&'static str>,
+  pub is_void: bool,
+}
+
+const fn element(name: &'static str, attributes: &'static [&'static str]) -> Element {
+  Element {
+    name,
+    attributes,
+    canonical_name: None,
+    is_void: false,
+  }
+}
+
+pub const ELEMENTS: [Element; 13] = [
+  element("a", &["href", "name", "target", "title"]),
+  element("b", &[]),
+  element("i", &[]),
+  Element {
+    canonical_name: Some("i"),
+    ..element("em", &[])
+  },
+  Element {
+    canonical_name: Some("b"),
+    ..element("strong", &[])
+  },
+  element("font", &["color", "size", "face"]),
+  element("p", &["align"]),
+  element("sup", &[]),
+  element("sub", &[]),
+  element("center", &[]),
+  Element {
+    is_void: true,
+    ..element("img", &["src", "alt", "width", "height"])
+  },
+  Element {
+    is_void: true,
+    ..element("br", &[])
+  },
+  Element {
+    is_void: true,
+    ..element("hr", &[])
+  },
+];
+
+pub fn get_element(name: &str) -> Option<&'static Element> {
+  ELEMENTS.iter().find(|element| element.name == name)
+}
+
+/// Splits a tag name that swallowed the first attribute name because of a
+/// missing space, like `ahref`, into the element and the attribute.
+pub fn split_glued_name(name: &str) -> Option<(&'static Element, &'static str)> {
+  ELEMENTS.iter().find_map(|element| {
+    let attribute = name.strip_prefix(element.name)?;
+    Some((element, correct_attribute_name(element, attribute)?))
+  })
+}
+
+/// Maps an attribute name to the attribute of the element it was most likely
+/// meant to be. Names one typo away from a known attribute, like `hrf` or
+/// `rhef` for `href`, are corrected. Returns `None` for unknown attributes.
+pub fn correct_attribute_name(element: &Element, name: &str) -> Option<&'static str> {
+  if let Some(attribute) = element.attributes.iter().find(|attribute| **attribute == name) {
+    return Some(attribute);
+  }
+  element
+    .attributes
+    .iter()
+    .find(|attribute| edit_distance(attribute, name) == 1)
+    .copied()
+}
+
+/// Number of insertions, deletions, substitutions and transpositions of
+/// adjacent characters needed to turn one word into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let a: Vec<char> = a.chars().collect();
+  let b: Vec<char> = b.chars().collect();
+  let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+  for (i, row) in distances.iter_mut().enumerate() {
+    row[0] = i;
+  }
+  distances[0] = (0..=b.len()).collect();
+  for i in 1..=a.len() {
+    for j in 1..=b.len() {
+      let cost = usize::from(a[i - 1] != b[j - 1]);
+      let mut distance = (distances[i - 1][j] + 1)
+        .min(distances[i][j - 1] + 1)
+        .min(distances[i - 1][j - 1] + cost);
+      if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+        distance = distance.min(distances[i - 2][j - 2] + 1);
+      }
+      distances[i][j] = distance;
+    }
+  }
+  distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn corrects_misspelled_attributes() {
+    let anchor = get_element("a").unwrap();
+    for misspelling in ["ref", "rhef", "hre", "hef", "hrf", "href"].iter() {
+      assert_eq!(correct_attribute_name(anchor, misspelling), Some("href"));
+    }
+    assert_eq!(correct_attribute_name(anchor, "nmae"), Some("name"));
+    assert_eq!(correct_attribute_name(anchor, "rel"), None);
+    assert_eq!(correct_attribute_name(anchor, "onclick"), None);
+  }
+
+  #[test]
+  fn splits_glued_names() {
+    let (element, attribute) = split_glued_name("ahref").unwrap();
+    assert_eq!(element.name, "a");
+    assert_eq!(attribute, "href");
+    let (element, attribute) = split_glued_name("imgsrc").unwrap();
+    assert_eq!(element.name, "img");
+    assert_eq!(attribute, "src");
+    assert!(split_glued_name("table").is_none());
+  }
+}
@@ -1,29 +1,299 @@
-mod opening_a_tag;
+mod elements;
+mod tokenizer;
 
-use opening_a_tag::normalize_opening_a_tag;
-use regex::Regex;
+use super::{normalize_url, NormalizationContext};
+use elements::{correct_attribute_name, get_element, split_glued_name, Element};
+use tokenizer::{tokenize, Attribute, Tag};
 
-pub fn normalize_html_tag(tag: &str) -> String {
-  let tag_syntax_good = Regex::new(r#"^(?:</?[a-z]+>|<a href="\S+?">)$"#)
-    .unwrap()
-    .is_match(tag);
-  if tag_syntax_good {
-    if tag == "</br>" {
-      return String::from("<br>");
+/// Attributes holding URLs, which are resolved against the page's base URL.
+const URL_ATTRIBUTES: [(&str, &str); 2] = [("a", "href"), ("img", "src")];
+
+/// Brings a tag into the normalized form `<name attribute="value">`. Known
+/// elements only keep their known attributes, in a fixed order, and misspelled
+/// attribute names are corrected. Returns the normalized tag and the rules of
+/// all repairs made.
+pub fn normalize_html_tag(tag: &str, context: &NormalizationContext) -> (String, Vec<&'static str>) {
+  let mut rules = Vec::new();
+  let mut parsed = tokenize(tag, &mut rules);
+
+  if get_element(&parsed.name).is_none() {
+    if let Some((element, attribute)) = split_glued_name(&parsed.name) {
+      let glued_name = parsed.name.clone();
+      if let Some(glued) = parsed
+        .attributes
+        .first_mut()
+        .filter(|glued| glued.name == glued_name)
+      {
+        glued.name = String::from(attribute);
+      }
+      parsed.name = String::from(element.name);
+      rules.push("missing-space");
+    }
+  }
+
+  let normalized = match get_element(&parsed.name) {
+    Some(element) => normalize_known_tag(element, parsed, &mut rules, context),
+    None => serialize(&parsed),
+  };
+
+  let mut distinct_rules = Vec::new();
+  for rule in rules {
+    if !distinct_rules.contains(&rule) {
+      distinct_rules.push(rule);
+    }
+  }
+  (normalized, distinct_rules)
+}
+
+/// Whether the element has no closing tag, like `br`.
+pub fn is_void_element(name: &str) -> bool {
+  get_element(name).is_some_and(|element| element.is_void)
+}
+
+fn normalize_known_tag(
+  element: &Element,
+  mut tag: Tag,
+  rules: &mut Vec<&'static str>,
+  context: &NormalizationContext,
+) -> String {
+  if let Some(canonical_name) = element.canonical_name {
+    tag.name = String::from(canonical_name);
+    rules.push("canonical-name");
+  }
+
+  if tag.is_closing {
+    if !tag.attributes.is_empty() {
+      rules.push("closing-tag-attributes");
+    }
+    if element.is_void {
+      rules.push("closing-void-element");
+      return match element.name {
+        // `</br>` is meant as line break by browsers as well.
+        "br" => String::from("<br>"),
+        _ => String::new(),
+      };
+    }
+    return format!("</{}>", tag.name);
+  }
+
+  let mut attributes: Vec<Attribute> = Vec::new();
+  for attribute in tag.attributes.iter() {
+    let name = match correct_attribute_name(element, &attribute.name) {
+      Some(name) => name,
+      None => {
+        rules.push("unknown-attribute");
+        continue;
+      }
+    };
+    if name != attribute.name {
+      rules.push("misspelled-attribute");
+    }
+    if attributes.iter().any(|known| known.name == name) {
+      rules.push("duplicate-attribute");
+      continue;
+    }
+    let value = attribute.value.clone().unwrap_or_default();
+    let value = match URL_ATTRIBUTES.contains(&(element.name, name)) {
+      true => normalize_url(&value, &context.base_url).unwrap_or_else(|warning| {
+        context.warn(warning);
+        value.split_whitespace().collect()
+      }),
+      false => value,
+    };
+    attributes.push(Attribute {
+      name: String::from(name),
+      value: Some(value),
+    });
+  }
+  attributes.sort_by_key(|attribute| {
+    element
+      .attributes
+      .iter()
+      .position(|known| *known == attribute.name)
+  });
+  tag.attributes = attributes;
+  serialize(&tag)
+}
+
+fn serialize(tag: &Tag) -> String {
+  let mut serialized = String::from("<");
+  if tag.is_closing {
+    serialized.push('/');
+  }
+  serialized.push_str(&tag.name);
+  if !tag.is_closing {
+    for attribute in tag.attributes.iter() {
+      serialized.push(' ');
+      serialized.push_str(&attribute.name);
+      if let Some(value) = attribute.value.as_ref() {
+        serialized.push_str(&format!(r#"="{}""#, value.replace('"', "&quot;")));
+      }
+    }
+  }
+  serialized.push('>');
+  serialized
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn normalize_html_tag(tag: &str) -> String {
+    super::normalize_html_tag(tag, &NormalizationContext::default()).0
+  }
+
+  fn get_rules(tag: &str) -> Vec<&'static str> {
+    super::normalize_html_tag(tag, &NormalizationContext::default()).1
+  }
+
+  #[test]
+  fn keeps_good_tags() {
+    assert_eq!(normalize_html_tag("<b>"), "<b>");
+    assert_eq!(normalize_html_tag("</i>"), "</i>");
+    assert!(get_rules("<br>").is_empty());
+  }
+
+  #[test]
+  fn changes_uppercase_to_lowercase() {
+    assert_eq!(
+      normalize_html_tag(r#"<A href="http://www.google.de">"#),
+      r#"<a href="http://www.google.de/">"#
+    );
+    assert_eq!(normalize_html_tag("</B>"), "</b>");
+  }
+
+  #[test]
+  fn inserts_missing_space_between_tag_name_and_href_attr() {
+    assert_eq!(
+      normalize_html_tag(r#"<ahref="http://www.google.de">"#),
+      r#"<a href="http://www.google.de/">"#
+    );
+    assert_eq!(get_rules(r#"<ahref="http://www.google.de/">"#), vec!["missing-space"]);
+  }
+
+  #[test]
+  fn fixes_bad_href_attr_name() {
+    for tag in [
+      r#"<a rhef="http://www.google.de">"#,
+      r#"<a ref="http://www.google.de">"#,
+      r#"<a hre="http://www.google.de">"#,
+      r#"<a hef="http://www.google.de">"#,
+      r#"<a hrf="http://www.google.de">"#,
+      r#"<a HREF="http://www.google.de">"#,
+    ]
+    .iter()
+    {
+      assert_eq!(
+        normalize_html_tag(tag),
+        r#"<a href="http://www.google.de/">"#
+      );
     }
-    return String::from(tag);
   }
 
-  let is_opening_a_tag = Regex::new(r"^<[aA](?:\s|href)").unwrap().is_match(tag);
-  if is_opening_a_tag {
-    return normalize_opening_a_tag(tag);
+  #[test]
+  fn inserts_missing_quotes() {
+    assert_eq!(
+      normalize_html_tag(r#"<a href=http://www.google.de>"#),
+      r#"<a href="http://www.google.de/">"#
+    );
+    assert_eq!(get_rules(r#"<a href=http://www.google.de/>"#), vec!["missing-quotes"]);
+  }
+
+  #[test]
+  fn fixes_a_closing_tag_as_end() {
+    assert_eq!(
+      normalize_html_tag(r#"<a href="http://www.google.de"</a>"#),
+      r#"<a href="http://www.google.de/">"#
+    );
+  }
+
+  #[test]
+  fn removes_spaces_around_equal_sign() {
+    assert_eq!(
+      normalize_html_tag(r#"<a href = "http://www.google.de">"#),
+      r#"<a href="http://www.google.de/">"#
+    );
+  }
+
+  #[test]
+  fn fixes_new_lines_in_tag() {
+    assert_eq!(
+      normalize_html_tag(
+        "<a href=\n\"https://www.smithsonianmag.com/history/decoding-antikythera-mechanism-first-computer-180953979/\"\n>"
+      ),
+      "<a href=\"https://www.smithsonianmag.com/history/decoding-antikythera-mechanism-first-computer-180953979/\">"
+    )
+  }
+
+  #[test]
+  fn resolves_relative_url() {
+    assert_eq!(
+      normalize_html_tag(r#"<a href="ap210101.html">"#),
+      r#"<a href="https://apod.nasa.gov/apod/ap210101.html">"#
+    );
+    assert_eq!(
+      normalize_html_tag(r#"<IMG SRC="image/2103/M31.jpg" ALT="M31" border=0>"#),
+      r#"<img src="https://apod.nasa.gov/apod/image/2103/M31.jpg" alt="M31">"#
+    );
+  }
+
+  #[test]
+  fn warns_about_unparseable_url() {
+    let context = NormalizationContext::default();
+    assert_eq!(
+      super::normalize_html_tag(r#"<a href="http://">"#, &context).0,
+      r#"<a href="http://">"#
+    );
+    assert_eq!(context.take_warnings().len(), 1);
   }
 
-  let is_closing_tag = tag.contains("/");
-  if is_closing_tag {
-    let tag_name = Regex::new(r"[^a-zA-Z]").unwrap().replace_all(tag, "");
-    return format!("</{}>", &tag_name.to_lowercase());
+  #[test]
+  fn keeps_anchor_attributes_in_order() {
+    assert_eq!(
+      normalize_html_tag(r#"<a target="_blank" TITLE="Home" href="ap210101.html" onclick="go()">"#),
+      r#"<a href="https://apod.nasa.gov/apod/ap210101.html" target="_blank" title="Home">"#
+    );
+    assert_eq!(normalize_html_tag(r#"<a name=top>"#), r#"<a name="top">"#);
+    assert_eq!(
+      get_rules(r#"<a href="ap210101.html" onclick="go()">"#),
+      vec!["unknown-attribute"]
+    );
   }
 
-  String::from(tag.to_lowercase())
+  #[test]
+  fn normalizes_formatting_elements() {
+    assert_eq!(normalize_html_tag("<EM>"), "<i>");
+    assert_eq!(normalize_html_tag("</strong>"), "</b>");
+    assert_eq!(
+      normalize_html_tag(r#"<font COLOR=red size="+1" style="x">"#),
+      r#"<font color="red" size="+1">"#
+    );
+    assert_eq!(normalize_html_tag(r#"<P ALIGN="center">"#), r#"<p align="center">"#);
+    assert_eq!(normalize_html_tag("<sup>"), "<sup>");
+  }
+
+  #[test]
+  fn fixes_void_elements() {
+    assert_eq!(normalize_html_tag("</br>"), "<br>");
+    assert_eq!(normalize_html_tag("<br />"), "<br>");
+    assert_eq!(normalize_html_tag("</img>"), "");
+    assert_eq!(get_rules("</br>"), vec!["closing-void-element"]);
+  }
+
+  #[test]
+  fn keeps_unknown_elements() {
+    assert_eq!(
+      normalize_html_tag(r#"<TABLE border=1>"#),
+      r#"<table border="1">"#
+    );
+  }
+
+  #[test]
+  fn reports_each_rule_once() {
+    assert_eq!(
+      get_rules(r#"<IMG SRC=image/M31.jpg ALT=M31>"#),
+      vec!["lowercase-name", "missing-quotes"]
+    );
+  }
 }
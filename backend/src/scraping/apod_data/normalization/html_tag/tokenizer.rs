@@ -0,0 +1,210 @@
+/// A tag as found in the page. Names are lowercased, attribute values are
+/// kept as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+  pub name: String,
+  pub is_closing: bool,
+  pub attributes: Vec<Attribute>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+  pub name: String,
+  pub value: Option<String>,
+}
+
+/// Parses a tag, recovering from the errors found in hand-written HTML:
+/// missing or unterminated quotes, a missing space between the tag name and
+/// the first attribute (`<ahref="...">`), spaces around `=`, XHTML-style
+/// slashes and markup that ended up inside the tag (`<a href="..."</a>`). The
+/// rule of every repair is added to `repairs`.
+pub fn tokenize(tag: &str, repairs: &mut Vec<&'static str>) -> Tag {
+  let inner = tag.trim_start_matches('<');
+  let inner = inner.strip_suffix('>').unwrap_or(inner).trim_start();
+  let (is_closing, inner) = match inner.strip_prefix('/') {
+    Some(inner) => (true, inner.trim_start()),
+    None => (false, inner),
+  };
+
+  let name_end = inner
+    .find(|c: char| !c.is_ascii_alphanumeric())
+    .unwrap_or(inner.len());
+  let name = &inner[..name_end];
+  if name.chars().any(|c| c.is_ascii_uppercase()) {
+    repairs.push("lowercase-name");
+  }
+  let mut rest = &inner[name_end..];
+  let mut attributes = Vec::new();
+
+  // A value directly following the name means the name swallowed the first
+  // attribute name. It is split up by the caller, which knows the elements.
+  if rest.trim_start().starts_with('=') {
+    let (value, remainder) = parse_value(rest.trim_start()[1..].trim_start(), repairs);
+    attributes.push(Attribute {
+      name: name.to_lowercase(),
+      value: Some(value),
+    });
+    rest = remainder;
+  }
+
+  loop {
+    rest = rest.trim_start();
+    let c = match rest.chars().next() {
+      Some(c) => c,
+      None => break,
+    };
+    match c {
+      '/' => {
+        if !is_closing {
+          repairs.push("self-closing-syntax");
+        }
+        rest = &rest[1..];
+      }
+      '<' => {
+        repairs.push("stray-markup");
+        break;
+      }
+      '"' | '\'' | '=' => {
+        repairs.push("stray-markup");
+        rest = &rest[1..];
+      }
+      _ => {
+        let attribute_end = rest
+          .find(|c: char| c.is_whitespace() || "=/<>\"'".contains(c))
+          .unwrap_or(rest.len());
+        let attribute_name = &rest[..attribute_end];
+        if attribute_name.chars().any(|c| c.is_ascii_uppercase()) {
+          repairs.push("lowercase-name");
+        }
+        rest = rest[attribute_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+          Some(remainder) => {
+            let (value, remainder) = parse_value(remainder.trim_start(), repairs);
+            rest = remainder;
+            Some(value)
+          }
+          None => None,
+        };
+        attributes.push(Attribute {
+          name: attribute_name.to_lowercase(),
+          value,
+        });
+      }
+    }
+  }
+
+  Tag {
+    name: name.to_lowercase(),
+    is_closing,
+    attributes,
+  }
+}
+
+/// Parses a quoted or unquoted attribute value and returns it together with
+/// the rest of the tag.
+fn parse_value<'a>(text: &'a str, repairs: &mut Vec<&'static str>) -> (String, &'a str) {
+  match text.chars().next() {
+    Some(quote) if quote == '"' || quote == '\'' => match text[1..].find(quote) {
+      Some(end) => (String::from(&text[1..end + 1]), &text[end + 2..]),
+      None => {
+        repairs.push("unterminated-quotes");
+        (String::from(text[1..].trim_end()), "")
+      }
+    },
+    _ => {
+      repairs.push("missing-quotes");
+      let end = text
+        .find(|c: char| c.is_whitespace() || c == '>')
+        .unwrap_or(text.len());
+      (String::from(&text[..end]), &text[end..])
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tokenize_with_repairs(tag: &str) -> (Tag, Vec<&'static str>) {
+    let mut repairs = Vec::new();
+    let tag = tokenize(tag, &mut repairs);
+    (tag, repairs)
+  }
+
+  fn attribute(name: &str, value: &str) -> Attribute {
+    Attribute {
+      name: String::from(name),
+      value: Some(String::from(value)),
+    }
+  }
+
+  #[test]
+  fn tokenizes_tag() {
+    let (tag, repairs) =
+      tokenize_with_repairs(r#"<a href="https://apod.nasa.gov/" title='APOD' download>"#);
+    assert_eq!(tag.name, "a");
+    assert!(!tag.is_closing);
+    assert_eq!(
+      tag.attributes,
+      vec![
+        attribute("href", "https://apod.nasa.gov/"),
+        attribute("title", "APOD"),
+        Attribute {
+          name: String::from("download"),
+          value: None
+        },
+      ]
+    );
+    assert!(repairs.is_empty());
+  }
+
+  #[test]
+  fn tokenizes_closing_tag() {
+    let (tag, _) = tokenize_with_repairs("</ B >");
+    assert_eq!(tag.name, "b");
+    assert!(tag.is_closing);
+  }
+
+  #[test]
+  fn recovers_from_missing_quotes() {
+    let (tag, repairs) = tokenize_with_repairs("<IMG SRC=image/m31.jpg alt = M31>");
+    assert_eq!(
+      tag.attributes,
+      vec![attribute("src", "image/m31.jpg"), attribute("alt", "M31")]
+    );
+    assert_eq!(
+      repairs,
+      vec!["lowercase-name", "lowercase-name", "missing-quotes", "missing-quotes"]
+    );
+  }
+
+  #[test]
+  fn recovers_from_unterminated_quotes() {
+    let (tag, repairs) = tokenize_with_repairs(r#"<a href="https://apod.nasa.gov/>"#);
+    assert_eq!(
+      tag.attributes,
+      vec![attribute("href", "https://apod.nasa.gov/")]
+    );
+    assert_eq!(repairs, vec!["unterminated-quotes"]);
+  }
+
+  #[test]
+  fn recovers_from_markup_in_tag() {
+    let (tag, repairs) = tokenize_with_repairs(r#"<a href="https://apod.nasa.gov/"</a>"#);
+    assert_eq!(
+      tag.attributes,
+      vec![attribute("href", "https://apod.nasa.gov/")]
+    );
+    assert_eq!(repairs, vec!["stray-markup"]);
+  }
+
+  #[test]
+  fn keeps_glued_attribute() {
+    let (tag, _) = tokenize_with_repairs(r#"<ahref="https://apod.nasa.gov/">"#);
+    assert_eq!(tag.name, "ahref");
+    assert_eq!(
+      tag.attributes,
+      vec![attribute("ahref", "https://apod.nasa.gov/")]
+    );
+  }
+}
@@ -0,0 +1,195 @@
+use super::html_tag::is_void_element;
+use regex::Regex;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// A problem found in normalized HTML.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintViolation {
+  /// Name of the failed check, like `multiple-spaces`.
+  pub check: &'static str,
+  pub message: &'static str,
+  /// Byte offset of the offending snippet in the linted HTML.
+  pub offset: usize,
+}
+
+impl Display for LintViolation {
+  fn fmt(&self, f: &mut Formatter) -> FmtResult {
+    write!(f, "{} at byte {}", self.message, self.offset)
+  }
+}
+
+/// Runs all checks on normalized HTML and returns every violation in order of
+/// appearance.
+pub fn lint_html(html: &str) -> Vec<LintViolation> {
+  let mut violations = Vec::new();
+  let mut violation = |check, message, offset| {
+    violations.push(LintViolation {
+      check,
+      message,
+      offset,
+    })
+  };
+
+  if html.starts_with(char::is_whitespace) {
+    violation("leading-whitespace", "Empty space at beginning of text", 0);
+  }
+  if let Some(last) = html.chars().last().filter(|last| last.is_whitespace()) {
+    violation(
+      "trailing-whitespace",
+      "Empty space at end of text",
+      html.len() - last.len_utf8(),
+    );
+  }
+  for found in Regex::new(r"</[bBiI]>\s*:").unwrap().find_iter(html) {
+    violation(
+      "colon-after-tag",
+      "Colon after closing </b> or </i> tag",
+      found.start(),
+    );
+  }
+  for found in Regex::new(r"\n{3,}").unwrap().find_iter(html) {
+    violation(
+      "multiple-newlines",
+      "More than 2 consecutive newlines",
+      found.start(),
+    );
+  }
+  for found in Regex::new(r" {2,}").unwrap().find_iter(html) {
+    violation(
+      "multiple-spaces",
+      "More than 1 consecutive space",
+      found.start(),
+    );
+  }
+
+  let valid_link = Regex::new(r#"^<a(?: [a-z]+="[^"]*")+>$"#).unwrap();
+  let valid_closing_tag = Regex::new(r"^</[a-zA-Z]+>$").unwrap();
+  let mut open_elements: Vec<(String, usize)> = Vec::new();
+  for tag in Regex::new(r"<[^>]*>").unwrap().find_iter(html) {
+    let text = tag.as_str();
+    let is_closing = text.starts_with("</");
+    let name: String = text
+      .trim_start_matches("</")
+      .trim_start_matches('<')
+      .chars()
+      .take_while(|c| c.is_ascii_alphanumeric())
+      .collect();
+
+    if name.chars().any(|c| c.is_ascii_uppercase()) {
+      violation("tag-case", "Upper case tag", tag.start());
+    }
+    if is_closing && !valid_closing_tag.is_match(text) {
+      violation(
+        "closing-tag-format",
+        "Closing tag with bad format",
+        tag.start(),
+      );
+    }
+    if !is_closing && (name == "a" || name.starts_with("ahref")) && !valid_link.is_match(text) {
+      violation(
+        "link-format",
+        "Link opening tag with bad format",
+        tag.start(),
+      );
+    }
+
+    let name = name.to_lowercase();
+    if name.is_empty() || is_void_element(&name) {
+      continue;
+    }
+    if !is_closing {
+      if name == "a" && open_elements.iter().any(|(open, _)| open == "a") {
+        violation("nested-links", "Link inside of a link", tag.start());
+      }
+      open_elements.push((name, tag.start()));
+      continue;
+    }
+    match open_elements.iter().rposition(|(open, _)| *open == name) {
+      Some(index) => {
+        if index + 1 != open_elements.len() {
+          violation(
+            "misnested-tags",
+            "Closing tag before the closing tags of elements opened inside",
+            tag.start(),
+          );
+        }
+        open_elements.remove(index);
+      }
+      None => violation(
+        "unbalanced-tags",
+        "Closing tag without opening tag",
+        tag.start(),
+      ),
+    }
+  }
+  for (_, offset) in open_elements {
+    violation("unbalanced-tags", "Opening tag without closing tag", offset);
+  }
+
+  violations.sort_by_key(|violation| violation.offset);
+  violations
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn get_checks(html: &str) -> Vec<(&'static str, usize)> {
+    lint_html(html)
+      .iter()
+      .map(|violation| (violation.check, violation.offset))
+      .collect()
+  }
+
+  #[test]
+  fn accepts_normalized_html() {
+    assert!(lint_html(
+      r#"<b>Explanation:</b> The <a href="https://apod.nasa.gov/">galaxy</a><br>is <i>far</i> away."#
+    )
+    .is_empty());
+  }
+
+  #[test]
+  fn reports_all_violations_with_offsets() {
+    assert_eq!(
+      get_checks(" A  <B>bold</B>: text "),
+      vec![
+        ("leading-whitespace", 0),
+        ("multiple-spaces", 2),
+        ("tag-case", 4),
+        ("colon-after-tag", 11),
+        ("tag-case", 11),
+        ("trailing-whitespace", 21),
+      ]
+    );
+  }
+
+  #[test]
+  fn reports_bad_tag_formats() {
+    assert_eq!(
+      get_checks(r#"<a href=x>link</a > <b>x</b/>"#),
+      vec![
+        ("link-format", 0),
+        ("closing-tag-format", 14),
+        ("closing-tag-format", 24),
+      ]
+    );
+  }
+
+  #[test]
+  fn reports_unbalanced_and_misnested_tags() {
+    assert_eq!(
+      get_checks(r#"<b>bold <i>both</b> none</i></sup> <i>open"#),
+      vec![
+        ("misnested-tags", 15),
+        ("unbalanced-tags", 28),
+        ("unbalanced-tags", 35),
+      ]
+    );
+    assert_eq!(
+      get_checks(r#"<a href="a">one <a href="b">two</a></a>"#),
+      vec![("nested-links", 16)]
+    );
+  }
+}
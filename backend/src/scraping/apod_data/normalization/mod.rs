@@ -1,6 +1,11 @@
+mod balance;
+mod context;
 mod html_tag;
+mod lint;
 mod text;
 mod url;
 
+pub use context::NormalizationContext;
+pub use lint::lint_html;
 pub use text::normalize_text;
-pub use url::normalize_url;
\ No newline at end of file
+pub use url::{get_base_url, normalize_url};
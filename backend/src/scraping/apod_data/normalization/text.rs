@@ -1,33 +1,68 @@
+use super::balance::balance_tags;
 use super::html_tag::normalize_html_tag;
-use crate::scraping::{ScrapeError, ScrapeResult};
+use super::{lint_html, NormalizationContext};
+use crate::apod::TextSection;
 use regex::{Captures, Regex};
 
-pub fn normalize_text(text: &str) -> String {
+/// Fixes the malformed HTML of a section and reports every repair made to the
+/// context. Problems the normalization could not fix are reported as warnings.
+pub fn normalize_text(text: &str, section: TextSection, context: &NormalizationContext) -> String {
   // TODO: Fix &ccedil; &oacute; &eacute; &aacute; &amp; &oslash;
   let new_lines_removed = Regex::new(r"\n+").unwrap().replace_all(text, " ");
 
   let tags_fixed = Regex::new(r"<[^>]+?>")
     .unwrap()
     .replace_all(&new_lines_removed, |captures: &Captures| {
-      normalize_html_tag(captures.get(0).unwrap().as_str())
+      let tag = captures.get(0).unwrap();
+      let (normalized, rules) = normalize_html_tag(tag.as_str(), context);
+      for rule in rules {
+        context.repair(rule, section, tag.start(), tag.as_str(), &normalized);
+      }
+      normalized
     });
 
-  let missing_closing_link_tag_fixed =
-    Regex::new(r"(?P<first_tag><a[^>]+?>)(?P<content>[^<]+?)(?P<add>[^\w]*)(?P<end>(?:<a|$))")
+  let repair_all = |text: &str, pattern: &str, replacement: &str, rule: &str| {
+    Regex::new(pattern)
       .unwrap()
-      .replace_all(&tags_fixed, "${first_tag}${content}</a>${add}${end}");
-
-  let non_content_moved_before = Regex::new(r#"(<(?:i|b|a href=".+?")>)(\s+)(\S)"#)
-    .unwrap()
-    .replace_all(&missing_closing_link_tag_fixed, "$2$1$3");
-
-  let non_content_moved_after = Regex::new(r#"(\S)(\s+)(</[iba]>)"#)
-    .unwrap()
-    .replace_all(&non_content_moved_before, "$1$3$2");
-
-  let tag_colon_order_fixed = Regex::new(r"(?P<c_tag></[ib]>)\s?:")
-    .unwrap()
-    .replace_all(&non_content_moved_after, ":${c_tag}");
+      .replace_all(text, |captures: &Captures| {
+        let found = captures.get(0).unwrap();
+        let mut repaired = String::new();
+        captures.expand(replacement, &mut repaired);
+        context.repair(rule, section, found.start(), found.as_str(), &repaired);
+        repaired
+      })
+      .to_string()
+  };
+
+  let missing_closing_link_tag_fixed = repair_all(
+    &tags_fixed,
+    r"(?P<first_tag><a[^>]+?>)(?P<content>[^<]+?)(?P<add>[^\w]*)(?P<end>(?:<a|$))",
+    "${first_tag}${content}</a>${add}${end}",
+    "missing-closing-link-tag",
+  );
+
+  let tags_balanced = balance_tags(&missing_closing_link_tag_fixed, section, context);
+
+  let non_content_moved_before = repair_all(
+    &tags_balanced,
+    r#"(<(?:i|b|a\s[^>]+)>)(\s+)(\S)"#,
+    "$2$1$3",
+    "whitespace-inside-tag",
+  );
+
+  let non_content_moved_after = repair_all(
+    &non_content_moved_before,
+    r#"(\S)(\s+)(</[iba]>)"#,
+    "$1$3$2",
+    "whitespace-inside-tag",
+  );
+
+  let tag_colon_order_fixed = repair_all(
+    &non_content_moved_after,
+    r"(?P<c_tag></[ib]>)\s?:",
+    ":${c_tag}",
+    "colon-after-tag",
+  );
 
   let multiple_spaces_fixed = Regex::new(r" {2,}")
     .unwrap()
@@ -39,120 +74,63 @@ pub fn normalize_text(text: &str) -> String {
 
   let trimmed = spaces_around_br_removed.trim();
 
-  match check_html(trimmed) {
-    Err(ScrapeError::HTMLFixing(err_message)) => {
-      panic!("HTML fix error ({}) in {}", err_message, trimmed)
-    }
-    _ => (),
+  for violation in lint_html(trimmed) {
+    context.warn(format!(
+      "HTML lint of normalized {}: {}",
+      section.as_str(),
+      violation
+    ));
   }
 
   String::from(trimmed)
 }
 
-fn check_html(html: &str) -> ScrapeResult<()> {
-  check_empty_start_of_text(html)?;
-  check_empty_end_of_text(html)?;
-  check_colon_style_tag_order(html)?;
-  check_multiple_new_lines(html)?;
-  check_multiple_spaces(html)?;
-  check_tag_case(html)?;
-  check_link_opening_tag_format(html)?;
-  check_closing_tag_format(html)
-}
-
-fn check_empty_start_of_text(html: &str) -> ScrapeResult<()> {
-  if Regex::new(r"^\s").unwrap().is_match(html) {
-    return Err(ScrapeError::HTMLFixing(String::from(
-      "Empty space at beginning of text",
-    )));
-  }
-  Ok(())
-}
-
-fn check_empty_end_of_text(html: &str) -> ScrapeResult<()> {
-  if Regex::new(r"\s$").unwrap().is_match(html) {
-    return Err(ScrapeError::HTMLFixing(String::from(
-      "Empty space at end of text",
-    )));
-  }
-  Ok(())
-}
-
-fn check_colon_style_tag_order(html: &str) -> ScrapeResult<()> {
-  if Regex::new(r"</[bi]>\s*:").unwrap().is_match(html) {
-    return Err(ScrapeError::HTMLFixing(String::from(
-      "Colon after closing </b> or </i> tag",
-    )));
-  }
-  Ok(())
-}
-
-fn check_multiple_new_lines(html: &str) -> ScrapeResult<()> {
-  if Regex::new(r"\n{3,}").unwrap().is_match(html) {
-    return Err(ScrapeError::HTMLFixing(String::from(
-      "More than 2 consecutive newlines",
-    )));
-  }
-  Ok(())
-}
-
-fn check_multiple_spaces(html: &str) -> ScrapeResult<()> {
-  if Regex::new(r" {2,}").unwrap().is_match(html) {
-    return Err(ScrapeError::HTMLFixing(String::from(
-      "More than 1 consecutive space",
-    )));
-  }
-  Ok(())
-}
-
-fn check_tag_case(html: &str) -> ScrapeResult<()> {
-  if Regex::new(r"</?[A-Z]").unwrap().is_match(html) {
-    return Err(ScrapeError::HTMLFixing(String::from("Upper case tag")));
-  }
-  Ok(())
-}
-
-fn check_link_opening_tag_format(html: &str) -> ScrapeResult<()> {
-  let num_any_link_tags = Regex::new(r"(?:<a\s|<ahref)")
-    .unwrap()
-    .find_iter(html)
-    .count();
-  let num_valid_link_tags = Regex::new(r#"<a href="\S+?">"#)
-    .unwrap()
-    .find_iter(html)
-    .count();
-  if num_any_link_tags != num_valid_link_tags {
-    return Err(ScrapeError::HTMLFixing(String::from(
-      "Link opening tag with bad format",
-    )));
-  }
-  Ok(())
-}
-
-fn check_closing_tag_format(html: &str) -> ScrapeResult<()> {
-  let all_closing_tags_valid = Regex::new(r"<\S+?>")
-    .unwrap()
-    .find_iter(html)
-    .filter(|tag| tag.as_str().contains("/"))
-    .all(|tag| Regex::new(r"</[a-z]+?>").unwrap().is_match(tag.as_str()));
-  if !all_closing_tags_valid {
-    return Err(ScrapeError::HTMLFixing(String::from(
-      "Closing tag with bad format",
-    )));
-  }
-  Ok(())
-}
-
 #[cfg(test)]
 mod tests {
   use super::*;
   use pretty_assertions::assert_eq;
 
+  fn normalize_text(text: &str) -> String {
+    super::normalize_text(
+      text,
+      TextSection::Explanation,
+      &NormalizationContext::default(),
+    )
+  }
+
+  #[test]
+  fn reports_repairs() {
+    let context = NormalizationContext::default();
+    super::normalize_text(
+      "See <B>this</B> and<i> that</i>: here",
+      TextSection::Meta,
+      &context,
+    );
+    let repairs = context.take_repairs();
+    let rules: Vec<&str> = repairs.iter().map(|repair| repair.rule.as_str()).collect();
+    assert_eq!(
+      rules,
+      vec![
+        "lowercase-name",
+        "lowercase-name",
+        "whitespace-inside-tag",
+        "colon-after-tag"
+      ]
+    );
+    assert_eq!(repairs[0].section, TextSection::Meta);
+    assert_eq!(repairs[0].offset, 4);
+    assert_eq!(repairs[0].before, "<B>");
+    assert_eq!(repairs[0].after, "<b>");
+    assert_eq!(repairs[1].offset, 11);
+    assert_eq!(repairs[2].before, "<i> t");
+    assert_eq!(repairs[2].after, " <i>t");
+  }
+
   #[test]
   fn fixes_tags() {
     assert_eq!(
       normalize_text("This is a text with a <a href=www.google.de>Link</a> within it."),
-      r#"This is a text with a <a href="www.google.de">Link</a> within it."#
+      r#"This is a text with a <a href="http://www.google.de/">Link</a> within it."#
     );
     assert_eq!(
       normalize_text("The <a href=\"https://en.wikipedia.org/wiki/Antikythera_mechanism\"\n>Antikythera mechanism</a>, pictured, is now widely regarded as the \n<a href=\"https://en.wikipedia.org/wiki/Computer#Pre-20th_century\"\n>first</a> <a href=\n\"https://www.smithsonianmag.com/history/decoding-antikythera-mechanism-first-computer-180953979/\"\n>computer</a>."),
@@ -164,19 +142,35 @@ mod tests {
     );
   }
 
+  #[test]
+  fn fixes_tags_with_attributes() {
+    assert_eq!(
+      normalize_text(r#"<A NAME=top>Top</A> and <EM>this</EM> <a href="ap210101.html" TARGET=_blank>link</a>"#),
+      r#"<a name="top">Top</a> and <i>this</i> <a href="https://apod.nasa.gov/apod/ap210101.html" target="_blank">link</a>"#
+    );
+  }
+
+  #[test]
+  fn balances_tags() {
+    assert_eq!(
+      normalize_text("<b>Explanation:</b> The <B><I>Crab Nebula</B> is</I> a <i>supernova remnant</b>."),
+      "<b>Explanation:</b> The <b><i>Crab Nebula</i></b> <i>is</i> a <i>supernova remnant.</i>"
+    );
+  }
+
   #[test]
   fn adds_missing_closing_link_tags() {
     assert_eq!(
       normalize_text(
-        r#"This is a text with a <a href="www.google.de">Link without end-tag <a href="www.google.de">and another Link</a>."#
+        r#"This is a text with a <a href="http://www.google.de/">Link without end-tag <a href="http://www.google.de/">and another Link</a>."#
       ),
-      r#"This is a text with a <a href="www.google.de">Link without end-tag</a> <a href="www.google.de">and another Link</a>."#
+      r#"This is a text with a <a href="http://www.google.de/">Link without end-tag</a> <a href="http://www.google.de/">and another Link</a>."#
     );
     assert_eq!(
       normalize_text(
-        r#"This is a text with a lonely <a href="www.google.de">Link without end-tag."#
+        r#"This is a text with a lonely <a href="http://www.google.de/">Link without end-tag."#
       ),
-      r#"This is a text with a lonely <a href="www.google.de">Link without end-tag</a>."#
+      r#"This is a text with a lonely <a href="http://www.google.de/">Link without end-tag</a>."#
     );
   }
 
@@ -247,32 +241,32 @@ mod tests {
   #[test]
   fn moves_spaces_out_of_a_to_front() {
     assert_eq!(
-      normalize_text(r#"Here is<a href="www.google.de"> Link</a> Text"#),
-      r#"Here is <a href="www.google.de">Link</a> Text"#
+      normalize_text(r#"Here is<a href="http://www.google.de/"> Link</a> Text"#),
+      r#"Here is <a href="http://www.google.de/">Link</a> Text"#
     );
   }
 
   #[test]
   fn moves_spaces_out_of_a_to_back() {
     assert_eq!(
-      normalize_text(r#"Here is <a href="www.google.de">Link </a>Text"#),
-      r#"Here is <a href="www.google.de">Link</a> Text"#
+      normalize_text(r#"Here is <a href="http://www.google.de/">Link </a>Text"#),
+      r#"Here is <a href="http://www.google.de/">Link</a> Text"#
     );
   }
 
   #[test]
   fn moves_spaces_out_of_a_to_front_and_back() {
     assert_eq!(
-      normalize_text(r#"Here is<a href="www.google.de"> Link </a>Text"#),
-      r#"Here is <a href="www.google.de">Link</a> Text"#
+      normalize_text(r#"Here is<a href="http://www.google.de/"> Link </a>Text"#),
+      r#"Here is <a href="http://www.google.de/">Link</a> Text"#
     );
   }
 
   #[test]
   fn leaves_spaces_as_is_in_good_a() {
     assert_eq!(
-      normalize_text(r#"Here is <a href="www.google.de">Link</a> Text"#),
-      r#"Here is <a href="www.google.de">Link</a> Text"#
+      normalize_text(r#"Here is <a href="http://www.google.de/">Link</a> Text"#),
+      r#"Here is <a href="http://www.google.de/">Link</a> Text"#
     );
   }
 
@@ -1,28 +1,73 @@
 use regex::Regex;
+use url::Url;
 
-pub fn normalize_url(url: &str) -> String {
-  let url_without_space = url.replace("\n", "").replace(" ", "").replace("\t", "");
-  let top_level_domain = r"[a-zA-Z]{2,}";
-  let domain_label = r"(?:[a-zA-Z0-9][a-zA-Z0-9\-]+?[a-zA-Z0-9]|[a-zA-Z0-9]{1,2})";
-  if url_without_space.starts_with("mailto:") {
-    url_without_space
-      .replace("@at@", "@")
-      .replace("[at]", "@")
-      .replace(".dot.", ".")
-      .replace("[dot]", ".")
-      .replace(".d.o.t.", ".")
-  } else {
-    let valid_full_url_regex = format!(
-      r"^(?:https?://)?(?:{}\.)+{}",
-      domain_label, top_level_domain
-    );
-    match Regex::new(valid_full_url_regex.as_str())
-      .unwrap()
-      .is_match(&url_without_space)
-    {
-      true => String::from(url_without_space),
-      false => format!("https://apod.nasa.gov/apod/{}", url_without_space),
+/// File extensions that look like top level domains, so that file names like
+/// `ap210101.html` are not mistaken for host names.
+const FILE_EXTENSIONS: [&str; 28] = [
+  "html", "htm", "shtml", "php", "asp", "aspx", "cgi", "pl", "jpg", "jpeg", "gif", "png", "tif",
+  "tiff", "bmp", "mpg", "mpeg", "mov", "avi", "mp4", "webm", "wmv", "txt", "pdf", "ps", "gz",
+  "zip", "swf",
+];
+
+/// Normalizes a link target and resolves it against the base URL of the page
+/// it was found on. Line breaks inside the URL are removed, obfuscated mailto
+/// addresses are restored and host names without a scheme get `http://`.
+/// URLs that cannot be parsed are returned as error with a warning message.
+pub fn normalize_url(url: &str, base_url: &Url) -> Result<String, String> {
+  let url = url.replace(['\n', '\r', '\t'], "");
+  let url = url.trim();
+  if url.is_empty() {
+    return Err(String::from("Empty URL"));
+  }
+
+  let url = match url.get(..7) {
+    Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => format!(
+      "mailto:{}",
+      url[7..]
+        .replace("@at@", "@")
+        .replace("[at]", "@")
+        .replace(".dot.", ".")
+        .replace("[dot]", ".")
+        .replace(".d.o.t.", ".")
+        .replace(' ', "")
+    ),
+    _ if !has_scheme(url) && !url.starts_with("//") && starts_with_host(url) => {
+      format!("http://{}", url)
     }
+    _ => String::from(url),
+  };
+
+  // Spaces are percent-encoded by the parser. Spaces breaking up a host name
+  // are line wrapping artifacts though, so they are removed as a fallback.
+  base_url
+    .join(&url)
+    .or_else(|_| base_url.join(&url.replace(' ', "")))
+    .map(|url| url.to_string())
+    .map_err(|err| format!("Could not parse URL '{}' ({})", url, err))
+}
+
+/// Gets the URL relative links of the page are resolved against, which is the
+/// page's own URL unless the page sets another one with a `<base>` tag.
+pub fn get_base_url(page: &str, page_url: &Url) -> Url {
+  Regex::new(r#"(?i)<base\s[^>]*?href\s*=\s*["']?(?P<url>[^"'\s>]+)"#)
+    .unwrap()
+    .captures(page)
+    .and_then(|captures| page_url.join(&captures["url"]).ok())
+    .unwrap_or_else(|| page_url.clone())
+}
+
+fn has_scheme(url: &str) -> bool {
+  Regex::new(r"^[a-zA-Z][a-zA-Z0-9+-]*:").unwrap().is_match(url)
+}
+
+fn starts_with_host(url: &str) -> bool {
+  let first_segment = url.split(['/', '?', '#']).next().unwrap_or(url);
+  let host_regex =
+    Regex::new(r"^(?:[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?\.)+(?P<tld>[a-zA-Z]{2,})(?::\d+)?$")
+      .unwrap();
+  match host_regex.captures(first_segment) {
+    Some(captures) => !FILE_EXTENSIONS.contains(&captures["tld"].to_lowercase().as_str()),
+    None => false,
   }
 }
 
@@ -30,47 +75,147 @@ pub fn normalize_url(url: &str) -> String {
 mod tests {
   use super::*;
 
+  fn normalize(url: &str) -> Result<String, String> {
+    normalize_url(
+      url,
+      &Url::parse("https://apod.nasa.gov/apod/ap210329.html").unwrap(),
+    )
+  }
+
   #[test]
   fn removes_whitespace() {
     assert_eq!(
-      normalize_url("http://www.google .de"),
-      "http://www.google.de"
+      normalize("http://www.google .de"),
+      Ok(String::from("http://www.google.de/"))
     );
   }
 
   #[test]
   fn removes_new_line() {
     assert_eq!(
-      normalize_url("http://www.google\n.de"),
-      "http://www.google.de"
+      normalize("http://www.google\n.de"),
+      Ok(String::from("http://www.google.de/"))
     );
   }
 
   #[test]
   fn fixes_at_in_mailto() {
     assert_eq!(
-      normalize_url("mailto:me@at@server.com"),
-      "mailto:me@server.com"
+      normalize("mailto:me@at@server.com"),
+      Ok(String::from("mailto:me@server.com"))
     );
     assert_eq!(
-      normalize_url("mailto:me[at]server.com"),
-      "mailto:me@server.com"
+      normalize("mailto:me[at]server.com"),
+      Ok(String::from("mailto:me@server.com"))
     );
   }
 
   #[test]
   fn fixes_dot_in_mailto() {
     assert_eq!(
-      normalize_url("mailto:me@server.dot.com"),
-      "mailto:me@server.com"
+      normalize("mailto:me@server.dot.com"),
+      Ok(String::from("mailto:me@server.com"))
+    );
+    assert_eq!(
+      normalize("mailto:me@server[dot]com"),
+      Ok(String::from("mailto:me@server.com"))
     );
     assert_eq!(
-      normalize_url("mailto:me@server[dot]com"),
-      "mailto:me@server.com"
+      normalize("mailto:me@server.d.o.t.com"),
+      Ok(String::from("mailto:me@server.com"))
     );
+  }
+
+  #[test]
+  fn resolves_relative_urls() {
+    assert_eq!(
+      normalize("image/2103/M31.jpg"),
+      Ok(String::from("https://apod.nasa.gov/apod/image/2103/M31.jpg"))
+    );
+    assert_eq!(
+      normalize("ap210101.html"),
+      Ok(String::from("https://apod.nasa.gov/apod/ap210101.html"))
+    );
+    assert_eq!(
+      normalize("../htmltest/gifcity/m31.html"),
+      Ok(String::from("https://apod.nasa.gov/htmltest/gifcity/m31.html"))
+    );
+    assert_eq!(
+      normalize("/apod/lib/about_apod.html"),
+      Ok(String::from("https://apod.nasa.gov/apod/lib/about_apod.html"))
+    );
+  }
+
+  #[test]
+  fn resolves_protocol_relative_urls() {
+    assert_eq!(
+      normalize("//www.youtube.com/embed/abc"),
+      Ok(String::from("https://www.youtube.com/embed/abc"))
+    );
+  }
+
+  #[test]
+  fn resolves_fragment_only_urls() {
+    assert_eq!(
+      normalize("#top"),
+      Ok(String::from("https://apod.nasa.gov/apod/ap210329.html#top"))
+    );
+  }
+
+  #[test]
+  fn keeps_other_schemes() {
+    assert_eq!(
+      normalize("ftp://ftp.example.org/pub/file.txt"),
+      Ok(String::from("ftp://ftp.example.org/pub/file.txt"))
+    );
+  }
+
+  #[test]
+  fn lowercases_scheme_and_host() {
+    assert_eq!(
+      normalize("HTTP://WWW.NASA.GOV/Image.JPG"),
+      Ok(String::from("http://www.nasa.gov/Image.JPG"))
+    );
+  }
+
+  #[test]
+  fn adds_scheme_to_host_names() {
+    assert_eq!(
+      normalize("www.google.de/search"),
+      Ok(String::from("http://www.google.de/search"))
+    );
+  }
+
+  #[test]
+  fn percent_encodes() {
+    assert_eq!(
+      normalize("image/2103/M31 big.jpg"),
+      Ok(String::from("https://apod.nasa.gov/apod/image/2103/M31%20big.jpg"))
+    );
+    assert_eq!(
+      normalize("https://de.wikipedia.org/wiki/Milchstraße"),
+      Ok(String::from("https://de.wikipedia.org/wiki/Milchstra%C3%9Fe"))
+    );
+    assert_eq!(
+      normalize("https://example.com/a%20b"),
+      Ok(String::from("https://example.com/a%20b"))
+    );
+  }
+
+  #[test]
+  fn reports_unparseable_urls() {
+    assert!(normalize("http://").is_err());
+    assert!(normalize("http://exa mple:99999/").is_err());
+    assert!(normalize(" \n").is_err());
+  }
+
+  #[test]
+  fn gets_base_url() {
+    let page_url = Url::parse("https://apod.nasa.gov/apod/ap210329.html").unwrap();
+    assert_eq!(get_base_url("<html><head></head>", &page_url), page_url);
     assert_eq!(
-      normalize_url("mailto:me@server.d.o.t.com"),
-      "mailto:me@server.com"
+      get_base_url(r#"<head><base href="/apod/old/"></head>"#, &page_url).as_str(),
+      "https://apod.nasa.gov/apod/old/"
     );
   }
 }
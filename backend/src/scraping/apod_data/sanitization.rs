@@ -0,0 +1,260 @@
+use super::getter::get_attribute;
+use regex::{Captures, Regex};
+
+/// Elements kept in sanitized HTML. All other elements are removed, but their
+/// content is kept.
+const ALLOWED_ELEMENTS: [&str; 11] = [
+  "a", "b", "i", "em", "strong", "u", "br", "p", "sup", "sub", "small",
+];
+
+/// Elements without closing tag.
+const VOID_ELEMENTS: [&str; 1] = ["br"];
+
+/// Elements that are removed together with their content.
+const DROPPED_ELEMENTS: [&str; 8] = [
+  "script", "style", "iframe", "object", "embed", "noscript", "template", "textarea",
+];
+
+/// URL schemes links may use. Relative URLs, like links to BPOD's own pages,
+/// are allowed as well.
+const ALLOWED_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// Makes HTML from upstream safe to serve: only allowlisted elements and
+/// attributes are kept, link targets with other schemes than `http`, `https`
+/// and `mailto` are removed, external links get `rel="noopener nofollow"` and
+/// unbalanced tags are closed or dropped.
+pub fn sanitize_html(html: &str) -> String {
+  let html = remove_dropped_elements(html);
+  let tag_regex =
+    Regex::new(r"<(?P<closing>/)?(?P<name>[a-zA-Z][a-zA-Z0-9]*)\b[^>]*>|<!--[\s\S]*?-->").unwrap();
+
+  let mut sanitized = String::new();
+  let mut open_elements: Vec<&str> = Vec::new();
+  let mut last_end = 0;
+  for captures in tag_regex.captures_iter(&html) {
+    let tag = captures.get(0).unwrap();
+    sanitized.push_str(&escape_text(&html[last_end..tag.start()]));
+    last_end = tag.end();
+
+    let name = match captures.name("name") {
+      Some(name) => name.as_str().to_lowercase(),
+      None => continue,
+    };
+    let name = match ALLOWED_ELEMENTS.iter().find(|allowed| **allowed == name) {
+      Some(name) => *name,
+      None => continue,
+    };
+
+    if captures.name("closing").is_some() {
+      if let Some(index) = open_elements.iter().rposition(|open| *open == name) {
+        for open in open_elements.drain(index..).rev() {
+          sanitized.push_str(&format!("</{}>", open));
+        }
+      }
+      continue;
+    }
+
+    let sanitized_tag = match name {
+      "a" => match sanitize_anchor(tag.as_str()) {
+        Some(anchor) => anchor,
+        // Anchors without a safe target become plain text, so their closing
+        // tag is dropped as unbalanced.
+        None => continue,
+      },
+      _ => format!("<{}>", name),
+    };
+    sanitized.push_str(&sanitized_tag);
+    if !VOID_ELEMENTS.contains(&name) {
+      open_elements.push(name);
+    }
+  }
+  sanitized.push_str(&escape_text(&html[last_end..]));
+  for open in open_elements.iter().rev() {
+    sanitized.push_str(&format!("</{}>", open));
+  }
+  sanitized
+}
+
+/// Removes dropped elements with everything inside them. An element that is
+/// never closed takes the rest of the text with it.
+fn remove_dropped_elements(html: &str) -> String {
+  let tag_regex = Regex::new(&format!(
+    r"(?i)<(?P<closing>/)?(?P<name>{})[\s/>]",
+    DROPPED_ELEMENTS.join("|")
+  ))
+  .unwrap();
+  let lowercase_html = html.to_ascii_lowercase();
+  let mut kept = String::new();
+  let mut position = 0;
+  while let Some(captures) = tag_regex.captures_at(html, position) {
+    let tag = captures.get(0).unwrap();
+    kept.push_str(&html[position..tag.start()]);
+    if captures.name("closing").is_some() {
+      position = html[tag.start()..]
+        .find('>')
+        .map_or(html.len(), |end| tag.start() + end + 1);
+      continue;
+    }
+    let closing_tag = format!("</{}", captures["name"].to_lowercase());
+    position = match lowercase_html[tag.end()..].find(&closing_tag) {
+      Some(start) => {
+        let start = tag.end() + start;
+        html[start..]
+          .find('>')
+          .map_or(html.len(), |end| start + end + 1)
+      }
+      None => html.len(),
+    };
+  }
+  kept.push_str(&html[position..]);
+  kept
+}
+
+fn sanitize_anchor(tag: &str) -> Option<String> {
+  let href = get_attribute(tag, "href").filter(|href| is_safe_url(href))?;
+  let mut anchor = format!(r#"<a href="{}""#, escape_attribute(&href));
+  if let Some(title) = get_attribute(tag, "title") {
+    anchor.push_str(&format!(r#" title="{}""#, escape_attribute(&title)));
+  }
+  if is_external(&href) {
+    anchor.push_str(r#" rel="noopener nofollow""#);
+  }
+  anchor.push('>');
+  Some(anchor)
+}
+
+/// Whether a link target is relative or uses an allowed scheme. Browsers
+/// ignore entities, whitespace and control characters within schemes, so
+/// `java&#x09;script:` has to be caught as well.
+fn is_safe_url(url: &str) -> bool {
+  let decoded: String = decode_entities(url)
+    .chars()
+    .filter(|c| !c.is_whitespace() && !c.is_control())
+    .collect();
+  match Regex::new(r"^(?P<scheme>[a-zA-Z][a-zA-Z0-9+.-]*):")
+    .unwrap()
+    .captures(&decoded)
+  {
+    Some(captures) => ALLOWED_SCHEMES.contains(&captures["scheme"].to_lowercase().as_str()),
+    None => !decoded.contains(':') || decoded.starts_with('/') || decoded.starts_with('#'),
+  }
+}
+
+fn is_external(url: &str) -> bool {
+  url.starts_with("//") || Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*:").unwrap().is_match(url)
+}
+
+fn decode_entities(text: &str) -> String {
+  Regex::new(r"(?i)&#(?:x(?P<hex>[0-9a-f]+)|(?P<dec>\d+));?")
+    .unwrap()
+    .replace_all(text, |captures: &Captures| {
+      let code = match (captures.name("hex"), captures.name("dec")) {
+        (Some(hex), _) => u32::from_str_radix(hex.as_str(), 16).ok(),
+        (_, Some(dec)) => dec.as_str().parse().ok(),
+        _ => None,
+      };
+      code
+        .and_then(char::from_u32)
+        .map(String::from)
+        .unwrap_or_default()
+    })
+    .replace("&colon;", ":")
+    .replace("&Tab;", "\t")
+    .replace("&NewLine;", "\n")
+}
+
+/// Escapes angle brackets that are not part of an allowed tag. Entities are
+/// kept as they are.
+fn escape_text(text: &str) -> String {
+  text.replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Escapes quotes, angle brackets and ampersands that do not start an entity.
+fn escape_attribute(value: &str) -> String {
+  Regex::new(r"&(?P<entity>[a-zA-Z]+;|#\d+;|#[xX][0-9a-fA-F]+;)?")
+    .unwrap()
+    .replace_all(value, |captures: &Captures| match captures.name("entity") {
+      Some(_) => String::from(&captures[0]),
+      None => String::from("&amp;"),
+    })
+    .replace('"', "&quot;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn keeps_allowed_elements() {
+    assert_eq!(
+      sanitize_html("A <b>bold</b> and <i>italic</i> text<br>with <sup>2</sup>"),
+      "A <b>bold</b> and <i>italic</i> text<br>with <sup>2</sup>"
+    );
+  }
+
+  #[test]
+  fn strips_unknown_elements_and_attributes() {
+    assert_eq!(
+      sanitize_html(r#"<center><font color="red">Red</font> <b onclick="alert(1)">text</b></center>"#),
+      "Red <b>text</b>"
+    );
+  }
+
+  #[test]
+  fn drops_scripts_and_frames_with_content() {
+    assert_eq!(
+      sanitize_html("Text<script>alert('<b>')</script> more<iframe src=\"https://evil.example\">x</iframe>."),
+      "Text more."
+    );
+    assert_eq!(sanitize_html("Text<style>b { color: red }"), "Text");
+  }
+
+  #[test]
+  fn adds_rel_to_external_links() {
+    assert_eq!(
+      sanitize_html(r#"<a href="https://www.nasa.gov/" target="_blank" title="NASA">NASA</a>"#),
+      r#"<a href="https://www.nasa.gov/" title="NASA" rel="noopener nofollow">NASA</a>"#
+    );
+    assert_eq!(
+      sanitize_html(r#"<a href="/apod/1999-01-05">yesterday</a>"#),
+      r#"<a href="/apod/1999-01-05">yesterday</a>"#
+    );
+  }
+
+  #[test]
+  fn removes_links_with_unsafe_schemes() {
+    assert_eq!(
+      sanitize_html(r#"<a href="javascript:alert(1)">click</a> here"#),
+      "click here"
+    );
+    assert_eq!(
+      sanitize_html(r#"<a href="java&#x09;script:alert(1)">click</a>"#),
+      "click"
+    );
+    assert_eq!(
+      sanitize_html(r#"<a href=" JaVaScRiPt:alert(1)">click</a>"#),
+      "click"
+    );
+    assert_eq!(
+      sanitize_html(r#"<a href="data:text/html;base64,PHNjcmlwdD4=">click</a>"#),
+      "click"
+    );
+  }
+
+  #[test]
+  fn escapes_attributes_and_stray_brackets() {
+    assert_eq!(
+      sanitize_html(r#"<a href="https://example.com/?a=1&b=2&amp;c=3">x</a> 1 < 2"#),
+      r#"<a href="https://example.com/?a=1&amp;b=2&amp;c=3" rel="noopener nofollow">x</a> 1 &lt; 2"#
+    );
+  }
+
+  #[test]
+  fn balances_tags() {
+    assert_eq!(sanitize_html("<b>bold <i>both</b> none</i>"), "<b>bold <i>both</i></b> none");
+    assert_eq!(sanitize_html("<p>open"), "<p>open</p>");
+  }
+}
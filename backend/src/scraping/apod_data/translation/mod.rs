@@ -15,7 +15,7 @@ pub fn html_to_markdown(html: &str) -> String {
     .unwrap()
     .replace_all(&italic_translated, "*$content*");
 
-  let links_translated = Regex::new(r#"<a href="(?P<url>.+?)">(?P<text>[\s\S]+?)</a>"#)
+  let links_translated = Regex::new(r#"<a href="(?P<url>[^"]+)"[^>]*>(?P<text>[\s\S]+?)</a>"#)
     .unwrap()
     .replace_all(&bold_translated, |captures: &Captures| {
       let url = captures.name("url").unwrap().as_str();
@@ -23,8 +23,22 @@ pub fn html_to_markdown(html: &str) -> String {
       format!("[{}]({})", text, url)
     });
 
+  let images_translated = Regex::new(r#"<img src="(?P<url>[^"]+)"(?: alt="(?P<alt>[^"]*)")?[^>]*>"#)
+    .unwrap()
+    .replace_all(&links_translated, "![$alt]($url)");
+
+  let paragraphs_translated = Regex::new(r"(?:\s*</?p(?: [^>]*)?>\s*)+")
+    .unwrap()
+    .replace_all(&images_translated, "\n\n");
+
+  // Anchors without target, fonts and super- and subscripts have no Markdown
+  // counterpart, so only their content is kept.
+  let presentational_removed = Regex::new(r"<a(?: [^>]*)?>|</?(?:font|sup|sub)(?: [^>]*)?>")
+    .unwrap()
+    .replace_all(&paragraphs_translated, "");
+
   let artifacts_removed = Regex::new(r#"\s?(?:</a>|</b>)\s?"#).unwrap().replace_all(
-    &links_translated,
+    &presentational_removed,
     |captures: &regex::Captures| match captures
       .get(0)
       .expect("Could not get artifact")
@@ -39,12 +53,12 @@ pub fn html_to_markdown(html: &str) -> String {
   let trimmed = artifacts_removed.trim();
 
   let test_re = Regex::new(r"(<|>|^\s|\s$|\*\s*:|\*:[^\s]|\n{3,}| {2,})").unwrap();
-  if test_re.is_match(&trimmed) {
-    let captures = test_re.captures(&trimmed).unwrap();
-    panic!(format!(
+  if test_re.is_match(trimmed) {
+    let captures = test_re.captures(trimmed).unwrap();
+    panic!(
       "Text not translated successfully - Found '{}' in '{}'\nUnformatted input: {}",
       &captures[1], trimmed, html
-    ));
+    );
   }
   String::from(trimmed)
 }
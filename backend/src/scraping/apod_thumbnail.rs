@@ -1,39 +1,225 @@
+use super::download;
 use super::error::{ScrapeError, ScrapeResult};
-use crate::apod::APOD;
+use super::video::{get_video_thumbnail_urls, get_vimeo_thumbnail_url};
+use crate::apod::{Video, VideoProvider, APOD};
+use crate::license_policy::LicensePolicy;
+use crate::original_store::hex_sha256;
+use crate::renditions::{encode, get_rendition_file_name, render, Rendition, RenditionSet};
+use crate::smart_crop::{Crop, CropRect};
+use crate::worker_pool::WorkerPool;
 use crate::APODRequestClient;
-use image::load_from_memory;
-use regex::Regex;
-use reqwest::Response;
-use std::path::Path;
-
-pub async fn get_apod_thumbnail(apod: &APOD, client: &APODRequestClient) -> ScrapeResult<()> {
-  let image_url = if apod.img_url.starts_with("https://www.youtube.com/embed") {
-    Regex::new(r#"https://www.youtube.com/embed/(.+?)(?:\?.*|$)"#)
-      .map(|re| match re.captures(&apod.img_url) {
-        Some(captures) => format!("https://img.youtube.com/vi/{}/0.jpg", &captures[1]),
-        None => panic!("No Youtube ID found in {}!", apod.img_url),
-      })
-      .unwrap()
-  } else if apod.img_url.starts_with("https://apod.nasa.gov/apod/image")
-    && !apod.img_url.contains(".swf")
-    && !apod.img_url.contains(".html")
-  {
-    String::from(&apod.img_url)
-  } else {
-    return Err(ScrapeError::ResourceUnsupported);
+use image::io::Reader;
+use image::{load_from_memory, DynamicImage, GenericImageView};
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+use url::Url;
+
+/// Generates the set of renditions of the APOD's image, or of a still of its
+/// video, and returns the files written and the crop of the square ones. A
+/// manual crop stored before is kept. Images are decoded and encoded on the
+/// worker pool.
+pub async fn get_apod_thumbnail(
+  apod: &APOD,
+  client: &APODRequestClient,
+  policy: &LicensePolicy,
+  renditions: &RenditionSet,
+  stored_crop: Option<Crop>,
+  pool: &WorkerPool,
+) -> ScrapeResult<(Vec<Rendition>, Crop)> {
+  if !policy.allows_thumbnail(&apod.license) {
+    return Err(ScrapeError::Unlicensed);
+  }
+  let media = apod.primary_media();
+  let img = match &media.video {
+    Some(video) => get_video_still(video, apod, client, policy, pool).await?,
+    None if is_image_url(&media.img.url) => download_image(&media.img.url, client, pool).await?,
+    None => return Err(ScrapeError::ResourceUnsupported),
   };
+  let date = apod.date.clone();
+  let renditions = renditions.clone();
+  pool
+    .run(move || {
+      let crop = Crop::choose(&img, renditions.crop_mode, stored_crop.as_ref());
+      Ok((write_renditions(&img, &date, &renditions, &crop.rect_for(img.dimensions()))?, crop))
+    })
+    .await
+}
 
-  let response: Response = client.get(&image_url).await?;
-  let img_bytes = response.bytes().await.map_err(|_| ScrapeError::Parsing)?;
-  let img = load_from_memory(&img_bytes).map_err(|_| ScrapeError::Image)?;
-  let thumbnail = img.resize_to_fill(250, 250, image::imageops::CatmullRom);
-  let thumbnail_file_name = format!("{}.png", apod.date);
-  let thumbnail_file_path = Path::new(&dirs::home_dir().unwrap())
-    .join("bpod")
-    .join(thumbnail_file_name);
-  thumbnail
-    .save_with_format(thumbnail_file_path, image::ImageFormat::Png)
-    .map_err(|_| ScrapeError::FileSystem)?;
+/// Builds the renditions from a stored original instead of downloading the
+/// image, marking them with the recipe. Originals that can not be decoded are
+/// returned as `ScrapeError::Image`, while renditions that can not be encoded
+/// are returned as `ScrapeError::Encoding`.
+pub async fn rebuild_thumbnail(
+  original_path: PathBuf,
+  date: String,
+  renditions: &RenditionSet,
+  stored_crop: Option<Crop>,
+  recipe: String,
+  pool: &WorkerPool,
+) -> ScrapeResult<(Vec<Rendition>, Crop)> {
+  let renditions = renditions.clone();
+  pool
+    .run(move || {
+      let img = Reader::open(&original_path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|_| ScrapeError::FileSystem)?
+        .decode()
+        .map_err(|_| ScrapeError::Image)?;
+      let crop = Crop::choose(&img, renditions.crop_mode, stored_crop.as_ref());
+      let mut written = write_renditions(&img, &date, &renditions, &crop.rect_for(img.dimensions()))?;
+      for rendition in written.iter_mut() {
+        rendition.recipe = Some(recipe.clone());
+      }
+      Ok((written, crop))
+    })
+    .await
+}
+
+pub fn rendition_exists(file_name: &str) -> bool {
+  get_file_path(file_name).is_file()
+}
+
+/// Deletes the file of a rendition, if it still exists.
+pub fn remove_rendition(file_name: &str) -> io::Result<()> {
+  match fs::remove_file(get_file_path(file_name)) {
+    Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+    _ => Ok(()),
+  }
+}
+
+/// Renders and encodes every shape of the set that fits the image in every
+/// format of the set.
+pub fn write_renditions(
+  img: &DynamicImage,
+  date: &str,
+  renditions: &RenditionSet,
+  crop: &CropRect,
+) -> ScrapeResult<Vec<Rendition>> {
+  let mut written = Vec::new();
+  for shape in renditions.shapes_for(img) {
+    let rendered = render(img, shape, crop);
+    for format in renditions.formats.iter() {
+      let bytes = encode(&rendered, *format).map_err(|_| ScrapeError::Encoding)?;
+      let file_name = get_rendition_file_name(date, shape, *format);
+      fs::write(get_file_path(&file_name), &bytes).map_err(|_| ScrapeError::FileSystem)?;
+      written.push(Rendition {
+        shape,
+        format: *format,
+        width: rendered.width(),
+        height: rendered.height(),
+        byte_size: bytes.len() as u64,
+        file_name,
+        recipe: None,
+      });
+    }
+  }
+  Ok(written)
+}
+
+/// Whether the URL points to an image hosted by APOD, rather than e.g. a
+/// Flash animation or an interactive page.
+pub fn is_image_url(url: &str) -> bool {
+  url.starts_with("https://apod.nasa.gov/apod/image") && !url.contains(".swf") && !url.contains(".html")
+}
 
-  Ok(())
+/// Gets a still image representing the video, trying the available thumbnail
+/// qualities from best to worst. Self-hosted videos without a poster image get
+/// a poster frame extracted from the video itself, which is kept as a file if
+/// the license policy permits rehosting.
+async fn get_video_still(
+  video: &Video,
+  apod: &APOD,
+  client: &APODRequestClient,
+  policy: &LicensePolicy,
+  pool: &WorkerPool,
+) -> ScrapeResult<DynamicImage> {
+  for url in get_video_thumbnail_urls(video) {
+    let img_url = match video.provider {
+      VideoProvider::Vimeo => match get_vimeo_thumbnail(&url, client).await {
+        Some(img_url) => img_url,
+        None => continue,
+      },
+      _ => url,
+    };
+    if let Ok(img) = download_image(&img_url, client, pool).await {
+      return Ok(img);
+    }
+  }
+
+  match video.provider {
+    VideoProvider::SelfHosted => {
+      let png = extract_poster_frame(&video.watch_url, client).await?;
+      let poster_path = get_file_path(&format!("{}-poster.png", apod.date));
+      let is_rehosted = policy.allows_rehosting(&apod.license);
+      pool
+        .run(move || {
+          if is_rehosted {
+            fs::write(poster_path, &png).map_err(|_| ScrapeError::FileSystem)?;
+          }
+          load_from_memory(&png).map_err(|_| ScrapeError::Image)
+        })
+        .await
+    }
+    _ => Err(ScrapeError::Network),
+  }
+}
+
+async fn get_vimeo_thumbnail(oembed_url: &str, client: &APODRequestClient) -> Option<String> {
+  let response = client.get(oembed_url).await.ok()?;
+  get_vimeo_thumbnail_url(&response.text().await.ok()?)
+}
+
+/// Downloads an image to a file named after the URL, so that an interrupted
+/// download is resumed by the next scrape, and decodes it.
+async fn download_image(url: &str, client: &APODRequestClient, pool: &WorkerPool) -> ScrapeResult<DynamicImage> {
+  // YouTube answers missing thumbnail qualities with a placeholder image and a
+  // 404 status, which the client returns as error instead of decoding it.
+  let file_name = hex_sha256(url.as_bytes());
+  let download = download::download_image(url, client, &get_file_path("downloads").join(file_name)).await?;
+  pool
+    .run(move || {
+      let file = File::open(&download.path).map_err(|_| ScrapeError::FileSystem)?;
+      let img = image::load(BufReader::new(file), download.format).map_err(|_| ScrapeError::Image);
+      let _ = fs::remove_file(&download.path);
+      img
+    })
+    .await
+}
+
+/// Extracts a frame from one second into the video as PNG using ffmpeg, which
+/// has to be installed. ffmpeg fetches the video itself, so the client checks
+/// robots.txt and holds a permit for the host meanwhile, and ffmpeg is killed
+/// once the host's request timeout is over.
+async fn extract_poster_frame(video_url: &str, client: &APODRequestClient) -> ScrapeResult<Vec<u8>> {
+  let url = Url::parse(video_url).map_err(|_| ScrapeError::Parsing)?;
+  let _permit = client.schedule(&url).await?;
+  let timeout = client.config.request_timeout(url.host_str().unwrap_or(""));
+  let ffmpeg = Command::new("ffmpeg")
+    .args(["-loglevel", "error", "-user_agent", &client.config.user_agent()])
+    .args(["-ss", "1", "-i", url.as_str()])
+    .args(["-frames:v", "1", "-f", "image2pipe", "-vcodec", "png", "-"])
+    .stdin(Stdio::null())
+    .kill_on_drop(true)
+    .output();
+  let output = match tokio::time::timeout(timeout, ffmpeg).await {
+    Ok(Ok(output)) => output,
+    Ok(Err(err)) if err.kind() == io::ErrorKind::NotFound => {
+      return Err(ScrapeError::MissingProgram("ffmpeg"))
+    }
+    Ok(Err(_)) => return Err(ScrapeError::ResourceUnsupported),
+    Err(_) => return Err(ScrapeError::Network),
+  };
+  if !output.status.success() {
+    return Err(ScrapeError::Image);
+  }
+  Ok(output.stdout)
+}
+
+fn get_file_path(file_name: &str) -> PathBuf {
+  Path::new(&dirs::home_dir().unwrap())
+    .join("bpod")
+    .join(file_name)
 }
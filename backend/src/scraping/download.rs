@@ -0,0 +1,406 @@
+use super::error::{ScrapeError, ScrapeResult};
+use crate::APODRequestClient;
+use image::ImageFormat;
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use reqwest::StatusCode;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Number of bytes needed to recognize all accepted image formats.
+const MAGIC_SIZE: usize = 12;
+
+/// Image formats accepted from upstream.
+const IMAGE_FORMATS: [ImageFormat; 6] = [
+  ImageFormat::Jpeg,
+  ImageFormat::Png,
+  ImageFormat::Gif,
+  ImageFormat::WebP,
+  ImageFormat::Tiff,
+  ImageFormat::Bmp,
+];
+
+/// An image downloaded completely to disk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+  pub path: PathBuf,
+  pub byte_size: u64,
+  pub format: ImageFormat,
+}
+
+/// How an attempt to download the rest of an image ended.
+enum Part {
+  /// The image is complete, with its size and first bytes.
+  Complete(u64, Vec<u8>),
+  /// The connection broke or stalled while streaming the body.
+  Interrupted,
+}
+
+/// Streams the image at `url` to `destination` without holding it in memory.
+/// Downloads larger than the client's maximum download size, with a content
+/// type other than an image, or with contents not starting like an accepted
+/// image format are aborted. Downloads time out when the server sends nothing
+/// for the request timeout, no matter how long they take as a whole.
+/// Interrupted downloads are resumed right away, as often as the retry policy
+/// allows, if the server supports ranges and the image did not change in
+/// between. Downloads still incomplete after that are kept as `.part` file
+/// and resumed by the next call.
+pub async fn download_image(
+  url: &str,
+  client: &APODRequestClient,
+  destination: &Path,
+) -> ScrapeResult<Download> {
+  let part_path = with_suffix(destination, ".part");
+  let validator_path = with_suffix(destination, ".part.validator");
+  if let Some(parent) = destination.parent() {
+    fs::create_dir_all(parent).map_err(|_| ScrapeError::FileSystem)?;
+  }
+
+  let policy = &client.retry_policy;
+  let mut num_interruptions = 0;
+  let (byte_size, head) = loop {
+    match download_part(url, client, &part_path, &validator_path).await? {
+      Part::Complete(byte_size, head) => break (byte_size, head),
+      Part::Interrupted if num_interruptions + 1 < policy.max_attempts => {
+        tokio::time::sleep(policy.backoff(num_interruptions)).await;
+        num_interruptions += 1;
+      }
+      Part::Interrupted => return Err(ScrapeError::Network),
+    }
+  };
+
+  let format = match detect_image_format(&head) {
+    Some(format) => format,
+    None => {
+      discard(&part_path, &validator_path);
+      return Err(ScrapeError::UnexpectedContent(String::from(
+        "no known image format",
+      )));
+    }
+  };
+  fs::rename(&part_path, destination).map_err(|_| ScrapeError::FileSystem)?;
+  let _ = fs::remove_file(&validator_path);
+  Ok(Download {
+    path: PathBuf::from(destination),
+    byte_size,
+    format,
+  })
+}
+
+/// Requests the part of the image not downloaded yet and appends it to the
+/// part file, or starts over if the download can not be resumed.
+async fn download_part(
+  url: &str,
+  client: &APODRequestClient,
+  part_path: &Path,
+  validator_path: &Path,
+) -> ScrapeResult<Part> {
+  let max_size = client.config.max_download_size;
+  let parsed_url = Url::parse(url).map_err(|_| ScrapeError::Parsing)?;
+  let idle_timeout = client.config.request_timeout(parsed_url.host_str().unwrap_or(""));
+
+  // Only downloads with a validator can be resumed, as the image might have
+  // changed otherwise.
+  let validator = fs::read_to_string(validator_path).ok();
+  let mut offset = match (validator.as_ref(), fs::metadata(part_path)) {
+    (Some(_), Ok(metadata)) => metadata.len(),
+    _ => 0,
+  };
+  let mut response = match client.get_from(url, offset, validator.as_deref()).await {
+    // The part file is at least as large as the image, so something is off
+    // with it.
+    Err(ScrapeError::Status(416)) if offset > 0 => {
+      offset = 0;
+      client.get_from(url, 0, None).await?
+    }
+    response => response?,
+  };
+  if response.status() != StatusCode::PARTIAL_CONTENT
+    || get_range_start(response.headers()) != Some(offset)
+  {
+    offset = 0;
+  }
+
+  check_content_type(response.headers())?;
+  let remaining_size = response
+    .headers()
+    .get(CONTENT_LENGTH)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.parse::<u64>().ok());
+  if remaining_size.is_some_and(|size| offset + size > max_size) {
+    discard(part_path, validator_path);
+    return Err(ScrapeError::TooLarge(max_size));
+  }
+  match get_validator(response.headers()) {
+    Some(validator) => fs::write(validator_path, validator),
+    None => fs::remove_file(validator_path).or(Ok(())),
+  }
+  .map_err(|_| ScrapeError::FileSystem)?;
+
+  let mut file = OpenOptions::new()
+    .create(true)
+    .write(true)
+    .append(offset > 0)
+    .truncate(offset == 0)
+    .open(part_path)
+    .map_err(|_| ScrapeError::FileSystem)?;
+  let mut head = read_head(part_path, offset)?;
+  let mut byte_size = offset;
+  let mut is_format_checked = false;
+  loop {
+    if !is_format_checked && head.len() >= MAGIC_SIZE {
+      if detect_image_format(&head).is_none() {
+        discard(part_path, validator_path);
+        return Err(ScrapeError::UnexpectedContent(String::from(
+          "no known image format",
+        )));
+      }
+      is_format_checked = true;
+    }
+    let chunk = match tokio::time::timeout(idle_timeout, response.chunk()).await {
+      Ok(Ok(Some(chunk))) => chunk,
+      Ok(Ok(None)) => break,
+      Ok(Err(_)) | Err(_) => return Ok(Part::Interrupted),
+    };
+    byte_size += chunk.len() as u64;
+    if byte_size > max_size {
+      discard(part_path, validator_path);
+      return Err(ScrapeError::TooLarge(max_size));
+    }
+    if head.len() < MAGIC_SIZE {
+      head.extend_from_slice(&chunk[..chunk.len().min(MAGIC_SIZE - head.len())]);
+    }
+    file.write_all(&chunk).map_err(|_| ScrapeError::FileSystem)?;
+  }
+  Ok(Part::Complete(byte_size, head))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+  let mut path = path.as_os_str().to_owned();
+  path.push(suffix);
+  PathBuf::from(path)
+}
+
+fn discard(part_path: &Path, validator_path: &Path) {
+  let _ = fs::remove_file(part_path);
+  let _ = fs::remove_file(validator_path);
+}
+
+/// Reads the first bytes of a partial download that is resumed.
+fn read_head(part_path: &Path, offset: u64) -> ScrapeResult<Vec<u8>> {
+  let mut head = Vec::new();
+  if offset > 0 {
+    File::open(part_path)
+      .and_then(|file| file.take(MAGIC_SIZE as u64).read_to_end(&mut head))
+      .map_err(|_| ScrapeError::FileSystem)?;
+  }
+  Ok(head)
+}
+
+/// Rejects responses that declare a content type other than an image, like
+/// HTML error pages. Generic binary types are left to the magic byte check.
+fn check_content_type(headers: &HeaderMap) -> ScrapeResult<()> {
+  let content_type = match headers.get(CONTENT_TYPE).and_then(|value| value.to_str().ok()) {
+    Some(content_type) => content_type.to_lowercase(),
+    None => return Ok(()),
+  };
+  let mime_type = content_type.split(';').next().unwrap_or("").trim();
+  match mime_type.starts_with("image/")
+    || mime_type == "application/octet-stream"
+    || mime_type == "binary/octet-stream"
+  {
+    true => Ok(()),
+    false => Err(ScrapeError::UnexpectedContent(String::from(mime_type))),
+  }
+}
+
+/// Recognizes accepted image formats by their leading bytes.
+fn detect_image_format(head: &[u8]) -> Option<ImageFormat> {
+  image::guess_format(head)
+    .ok()
+    .filter(|format| IMAGE_FORMATS.contains(format))
+    .filter(|format| *format != ImageFormat::WebP || head.get(8..12) == Some(b"WEBP"))
+}
+
+/// The strong validator identifying the version of a resource, for resuming
+/// with `If-Range`.
+fn get_validator(headers: &HeaderMap) -> Option<String> {
+  let etag = headers
+    .get(ETAG)
+    .and_then(|value| value.to_str().ok())
+    .filter(|etag| !etag.starts_with("W/"));
+  etag
+    .or_else(|| headers.get(LAST_MODIFIED).and_then(|value| value.to_str().ok()))
+    .map(String::from)
+}
+
+/// Gets the first byte position from a `Content-Range` header like
+/// `bytes 1024-2047/4096`.
+fn get_range_start(headers: &HeaderMap) -> Option<u64> {
+  headers
+    .get(CONTENT_RANGE)?
+    .to_str()
+    .ok()?
+    .strip_prefix("bytes ")?
+    .split('-')
+    .next()?
+    .trim()
+    .parse()
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::client_config::ClientConfig;
+  use crate::test_util::{headers, temp_dir, test_client};
+  use std::sync::{Arc, Mutex};
+  use std::time::Duration;
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+  use tokio::net::TcpListener;
+
+  const IMAGE: &[u8; 32] = b"\x89PNG\r\n\x1a\n0123456789abcdefghijklmn";
+
+  /// Serves `IMAGE` with an ETag, but stalls after the first half of the body
+  /// unless the request asks for a range. Returns the base URL and the
+  /// requests received, like `GET /m31.png bytes=16-`.
+  async fn start_stalling_server() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded_requests = requests.clone();
+    tokio::spawn(async move {
+      while let Ok((mut stream, _)) = listener.accept().await {
+        let recorded_requests = recorded_requests.clone();
+        tokio::spawn(async move {
+          let mut head = Vec::new();
+          let mut buffer = [0; 1024];
+          while !head.ends_with(b"\r\n\r\n") {
+            match stream.read(&mut buffer).await {
+              Ok(0) | Err(_) => return,
+              Ok(n) => head.extend_from_slice(&buffer[..n]),
+            }
+          }
+          let head = String::from_utf8_lossy(&head).to_lowercase();
+          let path = head.split(' ').nth(1).unwrap_or_default().to_string();
+          let range_start = head
+            .lines()
+            .find_map(|line| line.strip_prefix("range: bytes="))
+            .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok());
+          let request = match range_start {
+            Some(start) => format!("GET {} bytes={}-", path, start),
+            None => format!("GET {}", path),
+          };
+          recorded_requests.lock().unwrap().push(request);
+
+          let mut response = Vec::new();
+          match (path.as_str(), range_start) {
+            ("/m31.png", Some(start)) => {
+              response.extend_from_slice(
+                format!(
+                  "HTTP/1.1 206 Partial Content\r\nContent-Type: image/png\r\nETag: \"m31\"\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n\r\n",
+                  start,
+                  IMAGE.len() - 1,
+                  IMAGE.len(),
+                  IMAGE.len() - start
+                )
+                .as_bytes(),
+              );
+              response.extend_from_slice(&IMAGE[start..]);
+            }
+            ("/m31.png", None) => {
+              response.extend_from_slice(
+                format!(
+                  "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nETag: \"m31\"\r\nContent-Length: {}\r\n\r\n",
+                  IMAGE.len()
+                )
+                .as_bytes(),
+              );
+              response.extend_from_slice(&IMAGE[..IMAGE.len() / 2]);
+            }
+            _ => response.extend_from_slice(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"),
+          }
+          let _ = stream.write_all(&response).await;
+          tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+      }
+    });
+    (base_url, requests)
+  }
+
+  #[tokio::test]
+  async fn resumes_stalled_downloads() {
+    let (base_url, requests) = start_stalling_server().await;
+    let client = test_client(ClientConfig {
+      request_timeout: Duration::from_millis(300),
+      host_delay: Duration::from_millis(0),
+      ..ClientConfig::default()
+    });
+    let destination = temp_dir("download", "stalled").join("m31.png");
+    let download = download_image(&format!("{}/m31.png", base_url), &client, &destination)
+      .await
+      .unwrap();
+    assert_eq!(download.byte_size, IMAGE.len() as u64);
+    assert_eq!(download.format, ImageFormat::Png);
+    assert_eq!(fs::read(&destination).unwrap(), IMAGE.to_vec());
+    assert!(!with_suffix(&destination, ".part").exists());
+    assert_eq!(
+      *requests.lock().unwrap(),
+      vec!["GET /robots.txt", "GET /m31.png", "GET /m31.png bytes=16-"]
+    );
+    fs::remove_dir_all(destination.parent().unwrap()).unwrap();
+  }
+
+  #[test]
+  fn detects_image_formats() {
+    assert_eq!(
+      detect_image_format(b"\xFF\xD8\xFF\xE0\x00\x10JFIF\x00\x01"),
+      Some(ImageFormat::Jpeg)
+    );
+    assert_eq!(
+      detect_image_format(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0d"),
+      Some(ImageFormat::Png)
+    );
+    assert_eq!(detect_image_format(b"RIFF\x24\x00\x00\x00WEBP"), Some(ImageFormat::WebP));
+    assert_eq!(detect_image_format(b"RIFF\x24\x00\x00\x00WAVE"), None);
+    assert_eq!(detect_image_format(b"<!DOCTYPE html>"), None);
+  }
+
+  #[test]
+  fn checks_content_type() {
+    assert!(check_content_type(&headers(&[("content-type", "image/jpeg")])).is_ok());
+    assert!(check_content_type(&headers(&[("content-type", "application/octet-stream")])).is_ok());
+    assert!(check_content_type(&HeaderMap::new()).is_ok());
+    assert!(matches!(
+      check_content_type(&headers(&[("content-type", "text/html; charset=UTF-8")])),
+      Err(ScrapeError::UnexpectedContent(mime_type)) if mime_type == "text/html"
+    ));
+  }
+
+  #[test]
+  fn gets_strong_validators() {
+    assert_eq!(
+      get_validator(&headers(&[("etag", "\"5f3-1a\"")])),
+      Some(String::from("\"5f3-1a\""))
+    );
+    assert_eq!(
+      get_validator(&headers(&[
+        ("etag", "W/\"5f3-1a\""),
+        ("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+      ])),
+      Some(String::from("Wed, 21 Oct 2015 07:28:00 GMT"))
+    );
+    assert_eq!(get_validator(&HeaderMap::new()), None);
+  }
+
+  #[test]
+  fn parses_range_start() {
+    assert_eq!(
+      get_range_start(&headers(&[("content-range", "bytes 1024-2047/4096")])),
+      Some(1024)
+    );
+    assert_eq!(get_range_start(&headers(&[("content-range", "bytes */4096")])), None);
+    assert_eq!(get_range_start(&HeaderMap::new()), None);
+  }
+}
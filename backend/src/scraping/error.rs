@@ -7,22 +7,40 @@ pub enum ScrapeError {
   Parsing,
   ResourceUnsupported,
   FileSystem,
+  /// An image could not be decoded.
   Image,
-  HTMLFixing(String),
+  /// Renditions of a decoded image could not be encoded.
+  Encoding,
   Network,
+  /// The server answered with an error status, even after retrying.
+  Status(u16),
+  /// The site's robots.txt does not allow fetching the resource.
+  Disallowed,
+  /// The download exceeds the maximum size in bytes.
+  TooLarge(u64),
+  /// The response is not what was asked for, like an HTML page instead of an
+  /// image.
+  UnexpectedContent(String),
+  Unlicensed,
+  /// A program needed for the resource is not installed.
+  MissingProgram(&'static str),
 }
 
 impl Display for ScrapeError {
   fn fmt(&self, f: &mut Formatter) -> FmtResult {
-    match &*self {
+    match self {
       ScrapeError::Parsing => write!(f, "Parsing failed"),
       ScrapeError::ResourceUnsupported => write!(f, "The resource is unsupported"),
       ScrapeError::FileSystem => write!(f, "Could not save or load file"),
       ScrapeError::Image => write!(f, "Could not load image"),
-      ScrapeError::HTMLFixing(err_string) => {
-        write!(f, "HTML fixing was unsuccessful ({})", err_string)
-      }
+      ScrapeError::Encoding => write!(f, "Could not encode rendition"),
       ScrapeError::Network => write!(f, "The network resource could not be retrieved"),
+      ScrapeError::Status(status) => write!(f, "The server answered with status {}", status),
+      ScrapeError::Disallowed => write!(f, "The site's robots.txt disallows fetching the resource"),
+      ScrapeError::TooLarge(max_size) => write!(f, "The download exceeds the maximum size of {} bytes", max_size),
+      ScrapeError::UnexpectedContent(content) => write!(f, "Unexpected content: {}", content),
+      ScrapeError::Unlicensed => write!(f, "The license policy does not permit this use"),
+      ScrapeError::MissingProgram(program) => write!(f, "{} is required but not installed", program),
     }
   }
 }
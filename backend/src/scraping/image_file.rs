@@ -0,0 +1,102 @@
+use super::error::{ScrapeError, ScrapeResult};
+use crate::apod::ImageFile;
+use crate::APODRequestClient;
+use image::io::Reader as ImageReader;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE};
+use reqwest::StatusCode;
+use std::io::Cursor;
+
+/// Number of bytes fetched from an image to determine its dimensions. This
+/// covers the header of all common formats, including JPEGs with large EXIF
+/// blocks in front of the frame header.
+const PROBE_SIZE: u64 = 64 * 1024;
+
+const IMAGE_EXTENSIONS: [&str; 8] = [".jpg", ".jpeg", ".png", ".gif", ".tif", ".tiff", ".webp", ".bmp"];
+
+/// Collects the dimensions and byte size of the image at `url` by fetching only
+/// the beginning of the file. Resources that are not images (e.g. videos) are
+/// returned without any information.
+pub async fn get_image_file(url: &str, client: &APODRequestClient) -> ScrapeResult<ImageFile> {
+  let mut image_file = ImageFile::new(String::from(url));
+  if !is_image_url(url) {
+    return Ok(image_file);
+  }
+
+  let mut response = client.get_partial(url, PROBE_SIZE).await?;
+  image_file.byte_size = match response.status() {
+    StatusCode::PARTIAL_CONTENT => response
+      .headers()
+      .get(CONTENT_RANGE)
+      .and_then(|value| value.to_str().ok())
+      .and_then(parse_content_range_size),
+    StatusCode::OK => response
+      .headers()
+      .get(CONTENT_LENGTH)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.parse().ok()),
+    status => return Err(ScrapeError::Status(status.as_u16())),
+  };
+
+  let mut head: Vec<u8> = Vec::new();
+  while (head.len() as u64) < PROBE_SIZE {
+    match response.chunk().await.map_err(|_| ScrapeError::Network)? {
+      Some(chunk) => head.extend_from_slice(&chunk),
+      None => break,
+    }
+  }
+  if let Some((width, height)) = get_dimensions(&head) {
+    image_file.width = Some(width);
+    image_file.height = Some(height);
+  }
+
+  Ok(image_file)
+}
+
+fn is_image_url(url: &str) -> bool {
+  let path = url.split(['?', '#']).next().unwrap_or(url);
+  let path = path.to_lowercase();
+  IMAGE_EXTENSIONS.iter().any(|extension| path.ends_with(extension))
+}
+
+fn parse_content_range_size(content_range: &str) -> Option<u64> {
+  content_range.rsplit('/').next()?.trim().parse().ok()
+}
+
+fn get_dimensions(head: &[u8]) -> Option<(u32, u32)> {
+  ImageReader::new(Cursor::new(head))
+    .with_guessed_format()
+    .ok()?
+    .into_dimensions()
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn recognizes_image_urls() {
+    assert!(is_image_url("https://apod.nasa.gov/apod/image/2103/Arp273_2048.jpg"));
+    assert!(is_image_url("https://apod.nasa.gov/apod/image/2103/Arp273.JPG"));
+    assert!(is_image_url("https://apod.nasa.gov/apod/image/2103/Arp273.tif?x=1"));
+    assert!(!is_image_url("https://www.youtube.com/embed/abc?rel=0"));
+    assert!(!is_image_url("https://apod.nasa.gov/apod/image/2103/movie.mp4"));
+  }
+
+  #[test]
+  fn parses_total_size_from_content_range() {
+    assert_eq!(parse_content_range_size("bytes 0-65535/1234567"), Some(1234567));
+    assert_eq!(parse_content_range_size("bytes 0-65535/*"), None);
+  }
+
+  #[test]
+  fn gets_dimensions_from_truncated_image() {
+    let image = image::DynamicImage::new_rgb8(300, 200);
+    let mut bytes: Vec<u8> = Vec::new();
+    image
+      .write_to(&mut bytes, image::ImageOutputFormat::Png)
+      .unwrap();
+    bytes.truncate(64);
+    assert_eq!(get_dimensions(&bytes), Some((300, 200)));
+  }
+}
@@ -1,7 +1,14 @@
 mod apod_data;
 mod apod_thumbnail;
+mod download;
 mod error;
+mod image_file;
+mod original;
+mod retry;
+mod video;
 
-pub use apod_data::get_apod_data;
-pub use apod_thumbnail::get_apod_thumbnail;
+pub use apod_data::{get_apod_data, lint_html};
+pub use apod_thumbnail::{get_apod_thumbnail, rebuild_thumbnail, remove_rendition, rendition_exists};
 pub use error::{ScrapeError, ScrapeResult};
+pub use original::{get_apod_original, get_original_url};
+pub use retry::{RequestOutcome, RetryPolicy};
@@ -0,0 +1,86 @@
+use super::apod_thumbnail::is_image_url;
+use super::download::download_image;
+use super::error::{ScrapeError, ScrapeResult};
+use crate::apod::APOD;
+use crate::license_policy::LicensePolicy;
+use crate::original_store::{hex_sha256, Original, OriginalStore};
+use crate::worker_pool::WorkerPool;
+use crate::APODRequestClient;
+use image::io::Reader;
+use image::ImageFormat;
+use std::fs::{self, File};
+use std::io::BufReader;
+
+/// The URL of the largest version of the APOD's image, if it has one.
+pub fn get_original_url(apod: &APOD) -> Option<&str> {
+  let media = apod.primary_media();
+  if media.video.is_some() {
+    return None;
+  }
+  media
+    .hires_img
+    .as_ref()
+    .map(|img| img.url.as_str())
+    .filter(|url| is_image_url(url))
+    .or_else(|| Some(media.img.url.as_str()).filter(|url| is_image_url(url)))
+}
+
+/// Downloads the full-size image of the APOD into the originals store, if the
+/// license policy permits archiving it. Hashing the file runs on the worker
+/// pool.
+pub async fn get_apod_original(
+  apod: &APOD,
+  client: &APODRequestClient,
+  policy: &LicensePolicy,
+  store: &OriginalStore,
+  pool: &WorkerPool,
+) -> ScrapeResult<Original> {
+  if !policy.allows_archiving(&apod.license) {
+    return Err(ScrapeError::Unlicensed);
+  }
+  let url = get_original_url(apod).ok_or(ScrapeError::ResourceUnsupported)?;
+  let file_name = hex_sha256(url.as_bytes());
+  let download = download_image(url, client, &store.incoming_path(&file_name)).await?;
+
+  let store = store.clone();
+  pool
+    .run(move || {
+      let dimensions = File::open(&download.path)
+        .map_err(|_| ScrapeError::FileSystem)
+        .and_then(|file| {
+          Reader::with_format(BufReader::new(file), download.format)
+            .into_dimensions()
+            .map_err(|_| ScrapeError::Image)
+        });
+      let (width, height) = match dimensions {
+        Ok(dimensions) => dimensions,
+        Err(err) => {
+          let _ = fs::remove_file(&download.path);
+          return Err(err);
+        }
+      };
+      let sha256 = store
+        .add(&download.path)
+        .map_err(|_| ScrapeError::FileSystem)?;
+      Ok(Original {
+        sha256,
+        format: String::from(get_format_name(download.format)),
+        width,
+        height,
+        byte_size: download.byte_size,
+      })
+    })
+    .await
+}
+
+fn get_format_name(format: ImageFormat) -> &'static str {
+  match format {
+    ImageFormat::Jpeg => "jpeg",
+    ImageFormat::Png => "png",
+    ImageFormat::Gif => "gif",
+    ImageFormat::WebP => "webp",
+    ImageFormat::Tiff => "tiff",
+    ImageFormat::Bmp => "bmp",
+    _ => "other",
+  }
+}
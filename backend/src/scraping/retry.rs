@@ -0,0 +1,134 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::time::{Duration, SystemTime};
+
+/// What to do with the response to a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequestOutcome {
+  Success,
+  /// The error may be temporary, so the request is sent again.
+  Retry,
+  /// The request will not succeed when sent again.
+  Fail,
+}
+
+/// When and how often requests are sent again after failing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+  pub max_attempts: u32,
+  /// Delay before the second attempt, doubled for every further attempt.
+  pub base_delay: Duration,
+  /// Longest delay between two attempts. Servers asking to wait longer with
+  /// `Retry-After` are not retried.
+  pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    RetryPolicy {
+      max_attempts: 5,
+      base_delay: Duration::from_secs(1),
+      max_delay: Duration::from_secs(60),
+    }
+  }
+}
+
+impl RetryPolicy {
+  /// Server errors, rate limiting and request timeouts are retried, all other
+  /// client errors fail right away.
+  pub fn classify(&self, status: StatusCode) -> RequestOutcome {
+    match status {
+      StatusCode::TOO_MANY_REQUESTS | StatusCode::REQUEST_TIMEOUT => RequestOutcome::Retry,
+      status if status.is_server_error() => RequestOutcome::Retry,
+      status if status.is_client_error() => RequestOutcome::Fail,
+      _ => RequestOutcome::Success,
+    }
+  }
+
+  /// The delay before the attempt following `attempt`, counted from 0:
+  /// exponential backoff with jitter in the upper half of the delay, so
+  /// clients failing at the same time do not retry at the same time.
+  pub fn backoff(&self, attempt: u32) -> Duration {
+    let delay = self
+      .base_delay
+      .saturating_mul(2u32.saturating_pow(attempt))
+      .min(self.max_delay);
+    let half = delay / 2;
+    half + half.mul_f64(fastrand::f64())
+  }
+
+  /// The delay before retrying a response, which is the one the server asks for
+  /// with `Retry-After` if any. Returns `None` if the server asks to wait
+  /// longer than `max_delay`.
+  pub fn delay(&self, attempt: u32, headers: &HeaderMap) -> Option<Duration> {
+    match get_retry_after(headers, SystemTime::now()) {
+      Some(delay) if delay > self.max_delay => None,
+      Some(delay) => Some(delay),
+      None => Some(self.backoff(attempt)),
+    }
+  }
+}
+
+/// Parses the `Retry-After` header, which holds either a number of seconds or
+/// an HTTP date.
+fn get_retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+  let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+  if let Ok(seconds) = value.parse::<u64>() {
+    return Some(Duration::from_secs(seconds));
+  }
+  let date = httpdate::parse_http_date(value).ok()?;
+  Some(date.duration_since(now).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::headers;
+
+  #[test]
+  fn classifies_status_codes() {
+    let policy = RetryPolicy::default();
+    assert_eq!(policy.classify(StatusCode::OK), RequestOutcome::Success);
+    assert_eq!(policy.classify(StatusCode::NOT_MODIFIED), RequestOutcome::Success);
+    assert_eq!(policy.classify(StatusCode::NOT_FOUND), RequestOutcome::Fail);
+    assert_eq!(policy.classify(StatusCode::FORBIDDEN), RequestOutcome::Fail);
+    assert_eq!(policy.classify(StatusCode::TOO_MANY_REQUESTS), RequestOutcome::Retry);
+    assert_eq!(policy.classify(StatusCode::INTERNAL_SERVER_ERROR), RequestOutcome::Retry);
+    assert_eq!(policy.classify(StatusCode::SERVICE_UNAVAILABLE), RequestOutcome::Retry);
+  }
+
+  #[test]
+  fn backs_off_exponentially_with_jitter() {
+    let policy = RetryPolicy::default();
+    for (attempt, full_delay) in [(0, 1), (1, 2), (2, 4), (3, 8), (10, 60)].iter() {
+      let full_delay = Duration::from_secs(*full_delay);
+      let delay = policy.backoff(*attempt);
+      assert!(delay >= full_delay / 2 && delay <= full_delay);
+    }
+  }
+
+  #[test]
+  fn parses_retry_after() {
+    let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+    assert_eq!(
+      get_retry_after(&headers(&[("retry-after", "120")]), now),
+      Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+      get_retry_after(&headers(&[("retry-after", "Wed, 21 Oct 2015 07:28:30 GMT")]), now),
+      Some(Duration::from_secs(30))
+    );
+    assert_eq!(get_retry_after(&headers(&[("retry-after", "soon")]), now), None);
+    assert_eq!(get_retry_after(&HeaderMap::new(), now), None);
+  }
+
+  #[test]
+  fn gives_up_when_asked_to_wait_too_long() {
+    let policy = RetryPolicy::default();
+    assert_eq!(
+      policy.delay(0, &headers(&[("retry-after", "5")])),
+      Some(Duration::from_secs(5))
+    );
+    assert_eq!(policy.delay(0, &headers(&[("retry-after", "3600")])), None);
+  }
+}
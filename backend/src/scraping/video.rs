@@ -0,0 +1,156 @@
+use crate::apod::{Video, VideoProvider};
+use regex::Regex;
+
+/// YouTube thumbnail qualities, from best to worst. Not every video has the
+/// higher resolutions, so they have to be tried in order.
+const YOUTUBE_THUMBNAIL_QUALITIES: [&str; 5] = [
+  "maxresdefault",
+  "sddefault",
+  "hqdefault",
+  "mqdefault",
+  "0",
+];
+
+/// Recognizes a video by its URL and returns canonical watch and embed URLs
+/// for it. Returns `None` if the URL does not point to a supported video.
+pub fn parse_video_url(url: &str) -> Option<Video> {
+  let youtube_regex = Regex::new(
+    r"^(?:https?:)?//(?:www\.|m\.)?(?:youtube(?:-nocookie)?\.com/(?:embed/|v/|watch\?(?:[^#]*&)?v=)|youtu\.be/)(?P<id>[\w-]{11})",
+  )
+  .unwrap();
+  if let Some(captures) = youtube_regex.captures(url) {
+    let id = &captures["id"];
+    return Some(Video {
+      provider: VideoProvider::YouTube,
+      id: Some(String::from(id)),
+      watch_url: format!("https://www.youtube.com/watch?v={}", id),
+      embed_url: format!("https://www.youtube-nocookie.com/embed/{}", id),
+      poster_url: None,
+    });
+  }
+
+  let vimeo_regex =
+    Regex::new(r"^(?:https?:)?//(?:www\.|player\.)?vimeo\.com/(?:video/)?(?P<id>\d+)").unwrap();
+  if let Some(captures) = vimeo_regex.captures(url) {
+    let id = &captures["id"];
+    return Some(Video {
+      provider: VideoProvider::Vimeo,
+      id: Some(String::from(id)),
+      watch_url: format!("https://vimeo.com/{}", id),
+      embed_url: format!("https://player.vimeo.com/video/{}", id),
+      poster_url: None,
+    });
+  }
+
+  let self_hosted_regex = Regex::new(r"(?i)^https?://\S+\.(?:mp4|webm)(?:[?#]\S*)?$").unwrap();
+  if self_hosted_regex.is_match(url) {
+    return Some(Video {
+      provider: VideoProvider::SelfHosted,
+      id: None,
+      watch_url: String::from(url),
+      embed_url: String::from(url),
+      poster_url: None,
+    });
+  }
+
+  None
+}
+
+/// Returns the thumbnail URLs that can be derived from the video URL alone,
+/// ordered from best to worst quality.
+pub fn get_video_thumbnail_urls(video: &Video) -> Vec<String> {
+  match (&video.provider, &video.id) {
+    (VideoProvider::YouTube, Some(id)) => YOUTUBE_THUMBNAIL_QUALITIES
+      .iter()
+      .map(|quality| format!("https://img.youtube.com/vi/{}/{}.jpg", id, quality))
+      .collect(),
+    (VideoProvider::Vimeo, _) => vec![
+      format!(
+        "https://vimeo.com/api/oembed.json?url={}&width=1280",
+        video.watch_url
+      ),
+      format!("https://vimeo.com/api/oembed.json?url={}", video.watch_url),
+    ],
+    _ => video.poster_url.iter().cloned().collect(),
+  }
+}
+
+/// Extracts the thumbnail URL from a Vimeo oEmbed response.
+pub fn get_vimeo_thumbnail_url(oembed_json: &str) -> Option<String> {
+  Regex::new(r#""thumbnail_url"\s*:\s*"(?P<url>[^"]+)""#)
+    .unwrap()
+    .captures(oembed_json)
+    .map(|captures| captures["url"].replace("\\/", "/"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn recognizes_youtube_variants() {
+    let urls = [
+      "https://www.youtube.com/embed/dQw4w9WgXcQ?rel=0",
+      "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ",
+      "https://youtu.be/dQw4w9WgXcQ",
+      "https://www.youtube.com/watch?feature=share&v=dQw4w9WgXcQ",
+      "//www.youtube.com/v/dQw4w9WgXcQ",
+    ];
+    for url in urls.iter() {
+      let video = parse_video_url(url).expect(url);
+      assert_eq!(video.provider, VideoProvider::YouTube);
+      assert_eq!(video.id.as_deref(), Some("dQw4w9WgXcQ"));
+      assert_eq!(video.watch_url, "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+      assert_eq!(
+        video.embed_url,
+        "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ"
+      );
+    }
+  }
+
+  #[test]
+  fn recognizes_vimeo_player_urls() {
+    let video = parse_video_url("https://player.vimeo.com/video/123456789?title=0").unwrap();
+    assert_eq!(video.provider, VideoProvider::Vimeo);
+    assert_eq!(video.watch_url, "https://vimeo.com/123456789");
+    assert_eq!(video.embed_url, "https://player.vimeo.com/video/123456789");
+  }
+
+  #[test]
+  fn recognizes_self_hosted_videos() {
+    let video = parse_video_url("https://apod.nasa.gov/apod/image/2103/Eclipse.MP4").unwrap();
+    assert_eq!(video.provider, VideoProvider::SelfHosted);
+    assert_eq!(video.id, None);
+    assert!(parse_video_url("https://apod.nasa.gov/apod/image/2103/Loop.webm").is_some());
+  }
+
+  #[test]
+  fn ignores_images_and_pages() {
+    assert!(parse_video_url("https://apod.nasa.gov/apod/image/2103/M31.jpg").is_none());
+    assert!(parse_video_url("https://www.youtube.com/channel/abc").is_none());
+  }
+
+  #[test]
+  fn orders_youtube_thumbnails_by_quality() {
+    let video = parse_video_url("https://youtu.be/dQw4w9WgXcQ").unwrap();
+    let urls = get_video_thumbnail_urls(&video);
+    assert_eq!(
+      urls.first().unwrap(),
+      "https://img.youtube.com/vi/dQw4w9WgXcQ/maxresdefault.jpg"
+    );
+    assert_eq!(
+      urls.last().unwrap(),
+      "https://img.youtube.com/vi/dQw4w9WgXcQ/0.jpg"
+    );
+  }
+
+  #[test]
+  fn extracts_vimeo_thumbnail_url() {
+    assert_eq!(
+      get_vimeo_thumbnail_url(
+        r#"{"type":"video","thumbnail_url":"https:\/\/i.vimeocdn.com\/video\/1_1280.jpg","width":1280}"#
+      ),
+      Some(String::from("https://i.vimeocdn.com/video/1_1280.jpg"))
+    );
+  }
+}
@@ -0,0 +1,232 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+
+/// Longest side of the downscaled image saliency is computed on.
+const SALIENCY_SIZE: u32 = 200;
+
+/// A square region of an image, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropRect {
+  pub x: u32,
+  pub y: u32,
+  pub size: u32,
+}
+
+impl CropRect {
+  /// The largest square in the center of the image, as cut by
+  /// `resize_to_fill`.
+  pub fn center(width: u32, height: u32) -> CropRect {
+    let size = width.min(height);
+    CropRect {
+      x: (width - size) / 2,
+      y: (height - size) / 2,
+      size,
+    }
+  }
+
+  /// Maps the rectangle from an image of size `from` to the same region of an
+  /// image of size `to`, e.g. after the source image was replaced by a larger
+  /// version. If the aspect ratio changed, the region is no longer square, and
+  /// the largest square centered in it is used.
+  pub fn scale(&self, from: (u32, u32), to: (u32, u32)) -> CropRect {
+    if from == to {
+      return *self;
+    }
+    let x_factor = to.0 as f64 / from.0.max(1) as f64;
+    let y_factor = to.1 as f64 / from.1.max(1) as f64;
+    let width = self.size as f64 * x_factor;
+    let height = self.size as f64 * y_factor;
+    let size = (width.min(height).round() as u32).clamp(1, to.0.min(to.1));
+    let x = self.x as f64 * x_factor + (width - size as f64) / 2.0;
+    let y = self.y as f64 * y_factor + (height - size as f64) / 2.0;
+    CropRect {
+      x: (x.max(0.0).round() as u32).min(to.0 - size),
+      y: (y.max(0.0).round() as u32).min(to.1 - size),
+      size,
+    }
+  }
+}
+
+/// How square renditions are cropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CropMode {
+  Center,
+  /// Crop where the image is most salient.
+  Smart,
+}
+
+impl CropMode {
+  pub fn parse(mode: &str) -> Result<CropMode, String> {
+    match mode.trim() {
+      "center" => Ok(CropMode::Center),
+      "smart" => Ok(CropMode::Smart),
+      other => Err(format!("Unknown crop mode '{}'", other)),
+    }
+  }
+}
+
+/// The crop chosen for the square renditions of an APOD, together with the
+/// size of the image it was chosen on. Manual crops override the crop mode and
+/// keep the coordinates they were set in, so that rendering from images of
+/// other sizes does not change them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+  pub rect: CropRect,
+  pub source_width: u32,
+  pub source_height: u32,
+  pub is_manual: bool,
+}
+
+impl Crop {
+  /// Chooses the crop for an image, keeping a manual crop if there is one.
+  pub fn choose(img: &DynamicImage, mode: CropMode, stored: Option<&Crop>) -> Crop {
+    if let Some(stored) = stored.filter(|stored| stored.is_manual) {
+      return *stored;
+    }
+    let (width, height) = img.dimensions();
+    let rect = match mode {
+      CropMode::Center => CropRect::center(width, height),
+      CropMode::Smart => find_salient_crop(img),
+    };
+    Crop {
+      rect,
+      source_width: width,
+      source_height: height,
+      is_manual: false,
+    }
+  }
+
+  /// The crop rectangle mapped to an image of the given size.
+  pub fn rect_for(&self, (width, height): (u32, u32)) -> CropRect {
+    self
+      .rect
+      .scale((self.source_width, self.source_height), (width, height))
+  }
+}
+
+/// Finds the largest square with the most salient content. Astronomical
+/// images are mostly dark sky, so edges and regions brighter than the average
+/// mark where the subject is. The square slides along the longer side of the
+/// image, preferring the center among equally salient positions.
+pub fn find_salient_crop(img: &DynamicImage) -> CropRect {
+  let (width, height) = img.dimensions();
+  let center = CropRect::center(width, height);
+  if width == height {
+    return center;
+  }
+
+  let small = img
+    .resize(SALIENCY_SIZE, SALIENCY_SIZE, FilterType::Triangle)
+    .to_luma8();
+  let (small_width, small_height) = small.dimensions();
+  let is_landscape = width > height;
+  let (length, window) = match is_landscape {
+    true => (small_width, small_height),
+    false => (small_height, small_width),
+  };
+  if window >= length {
+    return center;
+  }
+
+  // Saliency summed across the shorter side.
+  let mean = small.pixels().map(|pixel| pixel[0] as f64).sum::<f64>() / small.len() as f64;
+  let luma = |x: u32, y: u32| small.get_pixel(x, y)[0] as f64;
+  let mut sums = vec![0.0; length as usize];
+  for y in 0..small_height {
+    for x in 0..small_width {
+      let dx = luma((x + 1).min(small_width - 1), y) - luma(x.saturating_sub(1), y);
+      let dy = luma(x, (y + 1).min(small_height - 1)) - luma(x, y.saturating_sub(1));
+      let saliency = dx.abs() + dy.abs() + (luma(x, y) - mean).max(0.0);
+      let position = if is_landscape { x } else { y };
+      sums[position as usize] += saliency;
+    }
+  }
+
+  let mut window_sum: f64 = sums[..window as usize].iter().sum();
+  let center_start = (length - window) as i64 / 2;
+  let mut best = (window_sum, -center_start.abs(), 0);
+  for start in 1..=(length - window) as usize {
+    window_sum += sums[start + window as usize - 1] - sums[start - 1];
+    let candidate = (window_sum, -(start as i64 - center_start).abs(), start);
+    if candidate.0 > best.0 + 1e-6 || ((candidate.0 - best.0).abs() <= 1e-6 && candidate.1 > best.1) {
+      best = candidate;
+    }
+  }
+
+  if best.2 as i64 == center_start {
+    return center;
+  }
+  let free_length = width.max(height) - center.size;
+  let offset = (best.2 as f64 * free_length as f64 / (length - window) as f64).round() as u32;
+  match is_landscape {
+    true => CropRect { x: offset, ..center },
+    false => CropRect { y: offset, ..center },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::{GrayImage, Luma};
+
+  /// A dark image with a bright disk, like a comet against the night sky.
+  fn sky_with_object(width: u32, height: u32, x: u32, y: u32) -> DynamicImage {
+    let mut img = GrayImage::from_pixel(width, height, Luma([10]));
+    for (pixel_x, pixel_y, pixel) in img.enumerate_pixels_mut() {
+      let distance = ((pixel_x as f64 - x as f64).powi(2) + (pixel_y as f64 - y as f64).powi(2)).sqrt();
+      if distance < 20.0 {
+        *pixel = Luma([240]);
+      }
+    }
+    DynamicImage::ImageLuma8(img)
+  }
+
+  fn contains(rect: &CropRect, x: u32, y: u32) -> bool {
+    rect.x + 20 <= x && x + 20 <= rect.x + rect.size && rect.y + 20 <= y && y + 20 <= rect.y + rect.size
+  }
+
+  #[test]
+  fn crops_around_salient_objects() {
+    for (width, height, x, y) in [(600, 200, 560, 100), (600, 200, 150, 100), (200, 600, 100, 30)].iter() {
+      let rect = find_salient_crop(&sky_with_object(*width, *height, *x, *y));
+      assert_eq!(rect.size, 200);
+      assert!(contains(&rect, *x, *y), "{:?} misses object at {}, {}", rect, x, y);
+      assert!(!contains(&CropRect::center(*width, *height), *x, *y));
+    }
+  }
+
+  #[test]
+  fn prefers_center_without_salient_objects() {
+    let img = DynamicImage::ImageLuma8(GrayImage::from_pixel(600, 200, Luma([10])));
+    assert_eq!(find_salient_crop(&img), CropRect::center(600, 200));
+  }
+
+  #[test]
+  fn scales_axes_separately() {
+    let rect = CropRect { x: 100, y: 50, size: 100 };
+    assert_eq!(rect.scale((400, 200), (800, 400)), CropRect { x: 200, y: 100, size: 200 });
+    // The width doubles but the height stays, so the region becomes 200x100.
+    assert_eq!(rect.scale((400, 200), (800, 200)), CropRect { x: 250, y: 50, size: 100 });
+    assert_eq!(rect.scale((400, 200), (400, 100)), CropRect { x: 125, y: 25, size: 50 });
+  }
+
+  #[test]
+  fn keeps_manual_crops() {
+    let img = sky_with_object(1200, 400, 1120, 200);
+    let manual = Crop {
+      rect: CropRect { x: 0, y: 0, size: 200 },
+      source_width: 600,
+      source_height: 200,
+      is_manual: true,
+    };
+    let crop = Crop::choose(&img, CropMode::Smart, Some(&manual));
+    assert_eq!(crop, manual);
+    assert_eq!(crop.rect_for(img.dimensions()), CropRect { x: 0, y: 0, size: 400 });
+    let automatic = Crop { is_manual: false, ..manual };
+    let crop = Crop::choose(&img, CropMode::Smart, Some(&automatic));
+    assert!(!crop.is_manual);
+    assert!(crop.rect.x + crop.rect.size >= 1140);
+    let crop = Crop::choose(&img, CropMode::Center, None);
+    assert_eq!(crop.rect, CropRect { x: 400, y: 0, size: 400 });
+  }
+}
@@ -0,0 +1,39 @@
+//! Fixtures shared by the tests of several modules.
+
+use crate::client_config::ClientConfig;
+use crate::APODRequestClient;
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A header map with the given names and values.
+pub fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+  let mut headers = HeaderMap::new();
+  for (name, value) in pairs.iter() {
+    headers.insert(*name, HeaderValue::from_static(value));
+  }
+  headers
+}
+
+/// An empty directory for the test `name` of `module`, unique to the process.
+/// Leftovers of earlier runs are removed.
+pub fn temp_dir(module: &str, name: &str) -> PathBuf {
+  let directory = env::temp_dir().join(format!("bpod-{}-{}-{}", module, name, std::process::id()));
+  let _ = fs::remove_dir_all(&directory);
+  fs::create_dir_all(&directory).unwrap();
+  directory
+}
+
+/// A client for stand-in servers on localhost, without HTTP cache and retrying
+/// failed requests right away.
+pub fn test_client(config: ClientConfig) -> APODRequestClient {
+  let config = ClientConfig {
+    cache_dir: None,
+    ..config
+  };
+  let mut client = APODRequestClient::new(config).unwrap();
+  client.retry_policy.base_delay = Duration::from_millis(1);
+  client
+}
@@ -0,0 +1,109 @@
+use std::env;
+use std::panic;
+use std::sync::Arc;
+use std::thread;
+use tokio::sync::Semaphore;
+use tokio::task;
+
+/// Runs CPU-bound work like decoding and encoding images on blocking threads,
+/// so that it does not stall the network I/O on the async runtime.
+#[derive(Clone)]
+pub struct WorkerPool {
+  workers: Arc<Semaphore>,
+  size: usize,
+}
+
+impl WorkerPool {
+  pub fn new(size: usize) -> WorkerPool {
+    let size = size.max(1);
+    WorkerPool {
+      workers: Arc::new(Semaphore::new(size)),
+      size,
+    }
+  }
+
+  /// Reads the number of workers from `BPOD_IMAGE_WORKERS`, using one worker
+  /// per CPU by default.
+  pub fn from_env() -> Result<WorkerPool, String> {
+    let size = match env::var("BPOD_IMAGE_WORKERS") {
+      Ok(size) => size
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|size| *size > 0)
+        .ok_or(format!("Invalid number of image workers '{}'", size))?,
+      Err(_) => thread::available_parallelism().map_or(1, |size| size.get()),
+    };
+    Ok(WorkerPool::new(size))
+  }
+
+  pub fn size(&self) -> usize {
+    self.size
+  }
+
+  /// Runs the job once a worker is free and returns its result. While all
+  /// workers are busy, callers wait instead of queueing more jobs, which
+  /// bounds the number of decoded images held in memory. Panics of the job
+  /// are passed on to the caller.
+  pub async fn run<T, F>(&self, job: F) -> T
+  where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+  {
+    let worker = self.workers.clone().acquire_owned().await.unwrap();
+    let result = task::spawn_blocking(move || {
+      let result = job();
+      drop(worker);
+      result
+    })
+    .await;
+    match result {
+      Ok(result) => result,
+      Err(err) => panic::resume_unwind(err.into_panic()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::time::Duration;
+
+  #[tokio::test]
+  async fn bounds_parallel_jobs() {
+    let pool = WorkerPool::new(2);
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+    let jobs: Vec<_> = (0..6)
+      .map(|i| {
+        let pool = pool.clone();
+        let running = running.clone();
+        let max_running = max_running.clone();
+        tokio::spawn(async move {
+          pool
+            .run(move || {
+              let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+              max_running.fetch_max(now_running, Ordering::SeqCst);
+              thread::sleep(Duration::from_millis(20));
+              running.fetch_sub(1, Ordering::SeqCst);
+              i * 2
+            })
+            .await
+        })
+      })
+      .collect();
+    let mut results = Vec::new();
+    for job in jobs {
+      results.push(job.await.unwrap());
+    }
+    assert_eq!(results, vec![0, 2, 4, 6, 8, 10]);
+    assert_eq!(max_running.load(Ordering::SeqCst), 2);
+  }
+
+  #[tokio::test]
+  #[should_panic(expected = "corrupt image")]
+  async fn passes_on_panics() {
+    WorkerPool::new(1).run(|| panic!("corrupt image")).await
+  }
+}